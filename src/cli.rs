@@ -0,0 +1,110 @@
+use std::error::Error;
+
+use clap::{Args, Parser, Subcommand};
+
+use crate::config::Config;
+use crate::hn_api::{get_comments, get_item, get_items, get_stories, get_user, ListType};
+use crate::output::{self, Format};
+use crate::session::Session;
+
+/// Browse Hacker News in the terminal. Without a subcommand the interactive UI is started.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+    /// Open an item on launch, given its id or a news.ycombinator.com/item?id=... link
+    #[arg(long, value_name = "ID|URL")]
+    pub open: Option<String>,
+    /// Start from the first tab instead of restoring where the last session left off
+    #[arg(long)]
+    pub fresh: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Print the top stories
+    Top(FeedArgs),
+    /// Print the newest stories
+    New(FeedArgs),
+    /// Print the best stories
+    Best(FeedArgs),
+    /// Print the latest Ask HN stories
+    Ask(FeedArgs),
+    /// Print the latest Show HN stories
+    Show(FeedArgs),
+    /// Print the latest job postings
+    Job(FeedArgs),
+    /// Print a single item, optionally with its comments
+    Item {
+        id: u64,
+        /// Include the full comment tree
+        #[arg(long)]
+        comments: bool,
+        #[arg(long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Print a user's profile
+    User {
+        name: String,
+        #[arg(long, value_enum, default_value = "table")]
+        format: Format,
+    },
+    /// Submit a story as the user in the [auth] config
+    Submit {
+        #[arg(long)]
+        title: String,
+        #[arg(long, default_value = "", conflicts_with = "text")]
+        url: String,
+        #[arg(long, default_value = "")]
+        text: String,
+    },
+}
+
+#[derive(Args)]
+pub struct FeedArgs {
+    /// Number of stories to print
+    #[arg(long, default_value_t = 30)]
+    limit: usize,
+    #[arg(long, value_enum, default_value = "table")]
+    format: Format,
+}
+
+fn feed(story_type: ListType, args: FeedArgs) -> Result<String, Box<dyn Error>> {
+    let ids = get_stories(&story_type)?;
+    let items = get_items(&ids[..args.limit.min(ids.len())])?;
+    Ok(output::stories(items.as_slice(), args.format))
+}
+
+/// Runs a non-interactive subcommand, printing its output to stdout
+pub fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    let out = match command {
+        Command::Top(args) => feed(ListType::TopStories, args)?,
+        Command::New(args) => feed(ListType::NewStories, args)?,
+        Command::Best(args) => feed(ListType::BestStories, args)?,
+        Command::Ask(args) => feed(ListType::AskStories, args)?,
+        Command::Show(args) => feed(ListType::ShowStories, args)?,
+        Command::Job(args) => feed(ListType::JobStories, args)?,
+        Command::Item { id, comments, format } => {
+            let item = get_item(id.to_string().as_str())?;
+            let tree = if comments {
+                let kids: Vec<i64> = item.get("kids")
+                    .and_then(|k| k.as_array())
+                    .map(|k| k.iter().filter_map(|id| id.as_i64()).collect())
+                    .unwrap_or_default();
+                Some(get_comments(kids.as_slice())?)
+            } else {
+                None
+            };
+            output::thread(&item, tree.as_deref(), format)
+        }
+        Command::User { name, format } => output::user(&get_user(name.as_str())?, format),
+        Command::Submit { title, url, text } => {
+            let mut session = Session::login(&Config::load().auth)?;
+            session.submit(title.as_str(), url.as_str(), text.as_str())?;
+            "Story submitted".to_string()
+        }
+    };
+    println!("{}", out);
+    Ok(())
+}
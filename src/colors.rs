@@ -2,6 +2,7 @@ use tui::style::{Color, Style};
 
 pub const HN_BACKGROUND: Color = Color::Rgb(246,246,239);
 pub const HN_ORANGE: Color = Color::Rgb(255,102,0);
+pub const HN_GREY: Color = Color::Rgb(130,130,130);
 
 pub enum HNStyles {
     OrangeBlock,
@@ -9,6 +10,7 @@ pub enum HNStyles {
     OrangeBorder,
     OrangeTitle,
     WhiteTitle,
+    Subtext,
 }
 
 pub fn get_style(style: HNStyles) -> Style {
@@ -18,6 +20,7 @@ pub fn get_style(style: HNStyles) -> Style {
         HNStyles::OrangeBorder => Style::default().bg(HN_BACKGROUND).fg(HN_ORANGE),
         HNStyles::OrangeTitle => Style::default().bg(HN_ORANGE).fg(HN_BACKGROUND),
         HNStyles::WhiteTitle => Style::default().bg(HN_BACKGROUND).fg(HN_ORANGE),
+        HNStyles::Subtext => Style::default().bg(HN_BACKGROUND).fg(HN_GREY),
     }
 }
 
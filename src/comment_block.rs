@@ -1,74 +1,345 @@
 use std::cmp::min;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::io::Error;
 
 use ammonia::Builder;
+use regex::Regex;
+use serde::Serialize;
 use serde_json::{Map, Value};
 use tui::backend::Backend;
 use tui::Frame;
 use tui::layout::{Alignment, Rect};
+use tui::style::{Modifier, Style};
 use tui::widgets::{Block, Borders, BorderType, Paragraph, Text};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::colors::{get_style, HNStyles};
-use crate::hn_api::get_comments;
+use crate::history;
+use crate::hn_api::{comment_from_json, get_comment_level, get_item, time_ago};
+use crate::output::plain_text;
+use crate::store::Item;
 
 const MAX_DEPTH: u16 = 10;
+// Replies fetched at a time while a thread loads in the background
+const LOAD_BATCH: usize = 30;
+// How far up `load_to` follows parents looking for a comment in the tree
+const MAX_ANCESTORS: usize = 100;
+#[derive(Clone, Serialize)]
 pub struct Comment {
+    pub id: i64,
+    #[serde(rename = "by")]
+    pub author: String,
+    pub time: i64,
     pub text: String,
     pub replies: Option<Vec<Comment>>,
+    // Ids of the replies. `replies` stays `None` until they are loaded
+    #[serde(skip)]
+    pub kids: Vec<i64>,
+    #[serde(skip)]
+    pub collapsed: bool,
+    // Posted from here but not seen in the API yet
+    #[serde(skip)]
+    pub pending: bool,
+    // What the API returned, empty for pending replies
+    #[serde(skip)]
+    pub item: Item,
+}
+
+impl Comment {
+    // Number of comments below this one, as far as they have been loaded
+    fn n_descendants(&self) -> usize {
+        match &self.replies {
+            Some(replies) => replies.iter().map(|r| 1 + r.n_descendants()).sum(),
+            None => self.kids.len(),
+        }
+    }
+
+    fn is_loaded(&self) -> bool {
+        self.replies.is_some() || self.kids.is_empty()
+    }
+}
+
+// A single wrapped row of the comment pane. Header rows remember the path to
+// their comment so that they can be clicked to collapse it
+struct CommentLine {
+    text: String,
+    header: Option<Vec<usize>>,
+    // Path to the comment whose replies this placeholder row stands for
+    loading: Option<Vec<usize>>,
+    // Id of the comment the row belongs to
+    id: i64,
 }
 
 pub struct CommentBlock {
     pub comments: Vec<Comment>,
-    pub comment_strings: Vec<String>,
     pub focused: bool,
-    scroll: u16,
+    lines: Vec<CommentLine>,
+    // Inner width the lines were wrapped for, 0 when they need to be rebuilt
+    width: u16,
+    // Body rows of each comment wrapped for `width`, so rebuilding the lines after a
+    // collapse or a batch of replies doesn't clean and wrap every comment again
+    wrapped: HashMap<i64, Vec<String>>,
+    // Text of each comment searched so far without its markup, so searching again doesn't
+    // clean every comment on every key
+    plain: HashMap<i64, String>,
+    builder: Builder<'static>,
+    scroll: usize,
+    // Rows of the pane when it was last drawn
+    height: u16,
+    // Comment that actions apply to, highlighted in the pane
+    selected: Option<i64>,
+    // Scroll to `selected` once the lines have been built for the pane's width
+    scroll_to_selected: bool,
+    // Pattern of the current search, its matches are highlighted
+    search: Option<Regex>,
+    // Comments whose replies `load_more` handed out to be fetched in the background
+    requested: HashSet<i64>,
+}
+
+// Index path through the reply tree to the comment with `id`
+fn find_path(comments: &[Comment], id: i64) -> Option<Vec<usize>> {
+    for (i, c) in comments.iter().enumerate() {
+        if c.id == id {
+            return Some(vec![i]);
+        }
+        if let Some(mut path) = c.replies.as_ref().and_then(|r| find_path(r.as_slice(), id)) {
+            path.insert(0, i);
+            return Some(path);
+        }
+    }
+    None
+}
+
+// Letters and digits of the HTML `text`, which survive HN formatting a posted reply, e.g.
+// `*word*` becoming italics and URLs becoming links
+fn comparable(text: &str) -> String {
+    plain_text(text).chars().filter(|c| c.is_alphanumeric()).collect()
+}
+
+// Ids of all comments in thread order, including collapsed ones, with whether they match
+// `pattern`. `plain` keeps the text of each comment without its markup for the next search
+fn search_helper(comments: &[Comment], pattern: &Regex, plain: &mut HashMap<i64, String>, out: &mut Vec<(i64, bool)>) {
+    for c in comments {
+        let text = plain.entry(c.id).or_insert_with(|| plain_text(c.text.as_str()));
+        let matched = pattern.is_match(c.author.as_str()) || pattern.is_match(text.as_str());
+        out.push((c.id, matched));
+        if let Some(replies) = &c.replies {
+            search_helper(replies.as_slice(), pattern, plain, out);
+        }
+    }
+}
+
+// Splits `row` so that the parts matching `pattern` stand out
+fn highlight(row: &str, style: Style, pattern: Option<&Regex>) -> Vec<Text<'static>> {
+    let mut texts = Vec::new();
+    let mut last = 0;
+    if let Some(pattern) = pattern {
+        for m in pattern.find_iter(row).filter(|m| !m.as_str().is_empty()) {
+            if m.start() > last {
+                texts.push(Text::styled(row[last..m.start()].to_string(), style));
+            }
+            texts.push(Text::styled(m.as_str().to_string(), get_style(HNStyles::OrangeTitle).modifier(Modifier::BOLD)));
+            last = m.end();
+        }
+    }
+    texts.push(Text::styled(format!("{}\n", &row[last..]), style));
+    texts
+}
+
+// Gives the comments whose replies are all among `items` their replies, returning whether any
+// did. Only comments without replies yet are filled in
+fn fill_replies(comments: &mut [Comment], items: &HashMap<i64, Item>) -> bool {
+    let mut found = false;
+    for c in comments.iter_mut() {
+        match c.replies.as_mut() {
+            Some(replies) => found |= fill_replies(replies, items),
+            None if !c.kids.is_empty() && c.kids.iter().all(|kid| items.contains_key(kid)) => {
+                c.replies = Some(c.kids.iter().map(|kid| comment_from_json(&items[kid])).collect());
+                found = true;
+            }
+            None => {}
+        }
+    }
+    found
+}
+
+// Paths to comments whose replies haven't been loaded, shallowest first, until they
+// have about `limit` replies between them
+fn unloaded_paths(comments: &[Comment], limit: usize) -> Vec<Vec<usize>> {
+    let mut found = Vec::new();
+    let mut n_replies = 0;
+    let mut level: Vec<(Vec<usize>, &Comment)> = comments.iter().enumerate().map(|(i, c)| (vec![i], c)).collect();
+    while !level.is_empty() && n_replies < limit {
+        let mut next = Vec::new();
+        for (path, c) in level {
+            match &c.replies {
+                Some(replies) => {
+                    for (i, reply) in replies.iter().enumerate() {
+                        let mut reply_path = path.clone();
+                        reply_path.push(i);
+                        next.push((reply_path, reply));
+                    }
+                }
+                None if !c.kids.is_empty() && n_replies < limit => {
+                    n_replies += c.kids.len();
+                    found.push(path);
+                }
+                None => {}
+            }
+        }
+        level = next;
+    }
+    found
+}
+
+/// Greedy word wrap of `text` into rows at most `width` columns wide. Words that don't fit
+/// in a row by themselves, such as long URLs, are cut where the row ends
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    for paragraph in text.lines() {
+        let mut row = String::new();
+        let mut used = 0;
+        for word in paragraph.split_whitespace() {
+            if used > 0 && used + 1 + word.width() > width {
+                rows.push(std::mem::take(&mut row));
+                used = 0;
+            }
+            if !row.is_empty() {
+                row.push(' ');
+                used += 1;
+            }
+            for c in word.chars() {
+                let w = c.width().unwrap_or(0);
+                if used + w > width && !row.is_empty() {
+                    rows.push(std::mem::take(&mut row));
+                    used = 0;
+                }
+                row.push(c);
+                used += w;
+            }
+        }
+        rows.push(row);
+    }
+    rows
 }
 
 impl CommentBlock {
-    fn helper(c: &Comment, depth: u16, builder: &Builder) -> Vec<String> {
+    fn helper(c: &Comment, path: &mut Vec<usize>, width: usize, builder: &Builder,
+              wrapped: &mut HashMap<i64, Vec<String>>, lines: &mut Vec<CommentLine>) {
+        let depth = path.len() as u16 - 1;
         let prefix = "--".repeat(min(depth, MAX_DEPTH) as usize);
-        let mut result = vec![format!("{}> {}\n", prefix, builder.clean(c.text.as_str()))];
-        match &c.replies {
-            Some(replies) => {
-                for reply in replies {
-                    result.append(&mut CommentBlock::helper(reply, depth + 1, builder));
-                }
+        let header = if c.collapsed {
+            format!("{}> [+] {} {} ({} more)", prefix, c.author, time_ago(c.time), c.n_descendants() + 1)
+        } else {
+            format!("{}> [-] {} {}", prefix, c.author, time_ago(c.time))
+        };
+        let header = if c.pending { format!("{} (sending)", header) } else { header };
+        lines.push(CommentLine { text: header, header: Some(path.clone()), loading: None, id: c.id });
+        if c.collapsed {
+            return;
+        }
+
+        let indent = " ".repeat(prefix.len() + 2);
+        let rows = wrapped.entry(c.id).or_insert_with(|| {
+            // Keep HN's paragraphs apart once the tags are stripped
+            let text = builder.clean(c.text.replace("<p>", "\n").as_str()).to_string();
+            wrap(text.as_str(), width.saturating_sub(indent.len()))
+                .into_iter()
+                .map(|row| format!("{}{}", indent, row))
+                .collect()
+        });
+        for row in rows.iter() {
+            lines.push(CommentLine { text: row.clone(), header: None, loading: None, id: c.id });
+        }
+        if !c.is_loaded() {
+            let n = c.kids.len();
+            let text = format!("{}  loading {} {}…", indent, n, if n == 1 { "reply" } else { "replies" });
+            lines.push(CommentLine { text, header: None, loading: Some(path.clone()), id: c.id });
+        }
+
+        if let Some(replies) = &c.replies {
+            for (i, reply) in replies.iter().enumerate() {
+                path.push(i);
+                CommentBlock::helper(reply, path, width, builder, wrapped, lines);
+                path.pop();
             }
-            None => {}
         }
-        return result;
     }
+
+    fn build_lines(&mut self) {
+        let mut lines = Vec::new();
+        for (i, c) in self.comments.iter().enumerate() {
+            CommentBlock::helper(c, &mut vec![i], self.width as usize, &self.builder, &mut self.wrapped, &mut lines);
+        }
+        self.lines = lines;
+    }
+
     pub fn new(item: &Map<String, Value>) -> Option<CommentBlock> {
         let comment_ids = match item.get("kids") {
             Some(kids) => kids.as_array().unwrap(),
-            None => return { None }
+            None => return None
         };
 
-        let comment_ids: Vec<i64> = comment_ids.into_iter().map(|id| id.as_i64().unwrap()).collect();
+        let comment_ids: Vec<i64> = comment_ids.iter().map(|id| id.as_i64().unwrap()).collect();
 
-        let comments = match get_comments(comment_ids.as_slice()) {
+        // Only the top level, replies load in the background once it is shown
+        let comments = match get_comment_level(comment_ids.as_slice()) {
             Ok(c) => c,
             Err(_) => {
                 return None;
             }
         };
+        Some(CommentBlock::from_comments(comments))
+    }
+
+    pub fn from_comments(comments: Vec<Comment>) -> CommentBlock {
         let mut builder = Builder::new();
-        let tag_cleaner = builder.tags(HashSet::new());
-        let mut comment_strings = Vec::new();
-        for c in &comments {
-            comment_strings.append(&mut CommentBlock::helper(c, 0, &tag_cleaner));
-        };
+        builder.tags(HashSet::new());
 
-        Some(CommentBlock {
+        CommentBlock {
             comments,
-            comment_strings,
             focused: false,
+            lines: Vec::new(),
+            width: 0,
+            wrapped: HashMap::new(),
+            plain: HashMap::new(),
+            builder,
             scroll: 0,
-        })
+            height: 0,
+            selected: None,
+            scroll_to_selected: false,
+            search: None,
+            requested: HashSet::new(),
+        }
     }
 
     pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, chunk: Rect) {
-        let comment_text: Vec<Text> = self.comment_strings.iter().map(|c| Text::raw(c)).collect();
+        self.height = chunk.height.saturating_sub(2);
+        let width = chunk.width.saturating_sub(2);
+        if width != self.width {
+            self.width = width;
+            self.wrapped.clear();
+            self.build_lines();
+        }
+        if self.scroll_to_selected {
+            self.scroll_to_selected = false;
+            if let Some(i) = self.lines.iter().position(|l| l.header.is_some() && Some(l.id) == self.selected) {
+                self.scroll = i;
+            }
+        }
+
+        // Only the rows on screen are styled and handed to the paragraph
+        let start = self.scroll.min(self.lines.len());
+        let end = (start + self.height as usize).min(self.lines.len());
+        let comment_text: Vec<Text> = self.lines[start..end].iter().flat_map(|l| {
+            let style = match l.header {
+                Some(_) if Some(l.id) == self.selected => get_style(HNStyles::OrangeTitle),
+                Some(_) => get_style(HNStyles::WhiteTitle),
+                None => get_style(HNStyles::WhiteBlock),
+            };
+            highlight(l.text.as_str(), style, self.search.as_ref())
+        }).collect();
 
         let mut block = Block::default()
             .title("Comments")
@@ -85,18 +356,373 @@ impl CommentBlock {
         let paragraph = Paragraph::new(comment_text.iter())
             .block(block)
             .style(get_style(HNStyles::WhiteBlock))
-            .alignment(Alignment::Left)
-            .wrap(true)
-            .scroll(self.scroll);
+            .alignment(Alignment::Left);
 
         f.render_widget(paragraph, chunk);
     }
+
+    /// Collapses or expands the comment whose header is on row `row` of the pane,
+    /// counted from the top border of `chunk`
+    pub fn click(&mut self, chunk: Rect, row: u16) {
+        if row <= chunk.y || row >= chunk.bottom().saturating_sub(1) {
+            return;
+        }
+        let index = self.scroll + (row - chunk.y - 1) as usize;
+        let path = match self.lines.get(index).and_then(|l| l.header.clone()) {
+            Some(p) => p,
+            None => return,
+        };
+        self.selected = self.lines[index].id.into();
+        self.toggle(path.as_slice());
+    }
+
+    /// Id of the highlighted comment
+    pub fn current(&self) -> Option<i64> {
+        self.selected
+    }
+
+    // Selects the comment at the top of the pane
+    fn select_top(&mut self) {
+        if let Some(l) = self.lines.get(self.scroll) {
+            self.selected = Some(l.id);
+        }
+    }
+
+    /// Highlights the comment with `id`, expanding its thread and scrolling to it
+    pub fn focus_comment(&mut self, id: i64) {
+        self.load_to(id);
+        let path = match find_path(self.comments.as_slice(), id) {
+            Some(p) => p,
+            None => return,
+        };
+        let mut comments = &mut self.comments;
+        for i in path {
+            let comment = &mut comments[i];
+            comment.collapsed = false;
+            comments = match comment.replies.as_mut() {
+                Some(r) => r,
+                None => break,
+            };
+        }
+        self.selected = Some(id);
+        self.scroll_to_selected = true;
+        self.build_lines();
+    }
+
+    fn find(&self, id: i64) -> Option<&Comment> {
+        let path = find_path(self.comments.as_slice(), id)?;
+        let mut comment = self.comments.get(path[0])?;
+        for i in &path[1..] {
+            comment = comment.replies.as_ref()?.get(*i)?;
+        }
+        Some(comment)
+    }
+
+    /// Raw HTML text of the comment with `id`
+    pub fn text_of(&self, id: i64) -> Option<&str> {
+        self.find(id).map(|c| c.text.as_str())
+    }
+
+    /// The comment with `id` as the API returned it
+    pub fn item_of(&self, id: i64) -> Option<&Item> {
+        self.find(id).map(|c| &c.item)
+    }
+
+    /// Whether the API already has `reply`, posted from here under the comment `parent`, or
+    /// under the story when there is no `parent`. It is matched by `id` when HN gave one, or
+    /// else by its author and text
+    pub fn has_reply(&self, parent: Option<i64>, id: Option<i64>, reply: &Comment) -> bool {
+        let replies = match parent {
+            Some(parent) => self.find(parent).and_then(|c| c.replies.as_ref()),
+            None => Some(&self.comments),
+        };
+        let text = comparable(reply.text.as_str());
+        replies.is_some_and(|r| r.iter().filter(|c| !c.pending).any(|c| match id {
+            Some(id) => c.id == id,
+            None => c.author == reply.author && comparable(c.text.as_str()) == text,
+        }))
+    }
+
+    /// Shows `reply` under the comment `parent`, or at the top of the thread when
+    /// `parent` is the story
+    pub fn add_reply(&mut self, parent: i64, reply: Comment) {
+        let id = reply.id;
+        match find_path(self.comments.as_slice(), parent) {
+            Some(path) => {
+                // The reply goes in front of the others, so they have to be there
+                let _ = self.load_replies(vec![path.clone()]);
+                let mut comment = &mut self.comments[path[0]];
+                for i in &path[1..] {
+                    comment = &mut comment.replies.as_mut().unwrap()[*i];
+                }
+                comment.collapsed = false;
+                comment.replies.get_or_insert_with(Vec::new).insert(0, reply);
+            }
+            None => self.comments.insert(0, reply),
+        }
+        self.selected = Some(id);
+        self.scroll_to_selected = true;
+        self.build_lines();
+    }
+
+    fn comment(&self, path: &[usize]) -> Option<&Comment> {
+        let mut comment = self.comments.get(*path.first()?)?;
+        for i in &path[1..] {
+            comment = comment.replies.as_ref()?.get(*i)?;
+        }
+        Some(comment)
+    }
+
+    fn comment_mut(&mut self, path: &[usize]) -> Option<&mut Comment> {
+        let mut comment = self.comments.get_mut(*path.first()?)?;
+        for i in &path[1..] {
+            comment = comment.replies.as_mut()?.get_mut(*i)?;
+        }
+        Some(comment)
+    }
+
+    fn toggle(&mut self, path: &[usize]) {
+        let expanded = match self.comment_mut(path) {
+            Some(comment) => {
+                comment.collapsed = !comment.collapsed;
+                !comment.collapsed
+            }
+            None => return,
+        };
+        // Expanding a thread shows its replies right away
+        if expanded {
+            let _ = self.load_replies(vec![path.to_vec()]);
+        }
+        self.build_lines();
+    }
+
+    /// Fetches the replies of the comments at `paths` that don't have them yet, keeping the
+    /// row at the top of the pane in place
+    fn load_replies(&mut self, paths: Vec<Vec<usize>>) -> Result<(), Error> {
+        let paths: Vec<Vec<usize>> = paths.into_iter()
+            .filter(|p| self.comment(p).is_some_and(|c| !c.is_loaded()))
+            .collect();
+        if paths.is_empty() {
+            return Ok(());
+        }
+        let ids: Vec<i64> = paths.iter()
+            .filter_map(|p| self.comment(p))
+            .flat_map(|c| c.kids.iter().copied())
+            .collect();
+        let mut loaded = get_comment_level(ids.as_slice())?.into_iter();
+        for path in &paths {
+            if let Some(c) = self.comment_mut(path) {
+                let n = c.kids.len();
+                c.replies = Some(loaded.by_ref().take(n).collect());
+            }
+        }
+        self.rebuild_lines();
+        Ok(())
+    }
+
+    // Builds the lines again after replies came in, keeping the row at the top of the pane in place
+    fn rebuild_lines(&mut self) {
+        let anchor = self.lines.get(self.scroll).map(|top| {
+            let first = self.lines.iter().position(|l| l.id == top.id).unwrap_or(0);
+            (top.id, self.scroll - first)
+        });
+        self.build_lines();
+        if let Some((id, offset)) = anchor {
+            if let Some(first) = self.lines.iter().position(|l| l.id == id) {
+                self.scroll = (first + offset).min(self.lines.len().saturating_sub(1));
+            }
+        }
+    }
+
+    /// Ids of the next batch of replies to fetch in the background, those waiting on screen
+    /// first and then breadth first. Empty while a batch is still out or once the whole thread
+    /// is loaded
+    pub fn load_more(&mut self) -> Vec<i64> {
+        if !self.requested.is_empty() {
+            return Vec::new();
+        }
+        let start = self.scroll.min(self.lines.len());
+        let end = (start + self.height as usize).min(self.lines.len());
+        let mut paths: Vec<Vec<usize>> = self.lines[start..end].iter().filter_map(|l| l.loading.clone()).collect();
+        if paths.is_empty() {
+            paths = unloaded_paths(self.comments.as_slice(), LOAD_BATCH);
+        }
+        let parents: Vec<&Comment> = paths.iter().filter_map(|p| self.comment(p)).collect();
+        let ids = parents.iter().flat_map(|c| c.kids.iter().copied()).collect();
+        self.requested = parents.iter().map(|c| c.id).collect();
+        ids
+    }
+
+    /// Fills in the replies fetched in the background, by id
+    pub fn receive(&mut self, items: &HashMap<i64, Item>) {
+        if fill_replies(self.comments.as_mut_slice(), items) {
+            self.rebuild_lines();
+        }
+        let requested = std::mem::take(&mut self.requested);
+        // Replies that came in some other way, or were collapsed away, aren't waited for
+        self.requested = requested.into_iter()
+            .filter(|id| self.find(*id).is_some_and(|c| !c.is_loaded()))
+            .collect();
+    }
+
+    /// Loads every reply that hasn't been loaded yet
+    pub fn load_all(&mut self) -> Result<(), Error> {
+        loop {
+            let paths = unloaded_paths(self.comments.as_slice(), usize::MAX);
+            if paths.is_empty() {
+                return Ok(());
+            }
+            self.load_replies(paths)?;
+        }
+    }
+
+    // Loads the replies along the way from the tree down to comment `id`, found by following
+    // its parents up to a comment that is already loaded
+    fn load_to(&mut self, id: i64) {
+        let mut chain = vec![id];
+        while let Some(last) = chain.last().copied() {
+            if find_path(self.comments.as_slice(), last).is_some() || chain.len() > MAX_ANCESTORS {
+                break;
+            }
+            let parent = history::cached(last)
+                .or_else(|| get_item(last.to_string().as_str()).ok())
+                .and_then(|item| item.get("parent").and_then(|p| p.as_i64()));
+            match parent {
+                Some(p) => chain.push(p),
+                None => return,
+            }
+        }
+        for ancestor in chain.iter().skip(1).rev() {
+            match find_path(self.comments.as_slice(), *ancestor) {
+                Some(path) => {
+                    if self.load_replies(vec![path]).is_err() {
+                        return;
+                    }
+                }
+                None => return,
+            }
+        }
+    }
+
+    pub fn scroll_position(&self) -> usize {
+        self.scroll
+    }
+
+    /// Scrolls to `scroll` rows from the top, instead of to the selected comment
+    pub fn set_scroll_position(&mut self, scroll: usize) {
+        self.scroll = scroll;
+        self.scroll_to_selected = false;
+    }
+
+    /// Highlights matches of `pattern`, returning how many comments match by author or text
+    pub fn search(&mut self, pattern: Option<&Regex>) -> usize {
+        self.search = pattern.cloned();
+        let mut comments = Vec::new();
+        if let Some(pattern) = pattern {
+            search_helper(self.comments.as_slice(), pattern, &mut self.plain, &mut comments);
+        }
+        comments.iter().filter(|(_, matched)| *matched).count()
+    }
+
+    /// Focuses the next matching comment after the selected one, or the previous one when
+    /// not `forward`, wrapping around and expanding collapsed threads to show it. With
+    /// `inclusive` the selected comment itself counts. Returns false when nothing matches
+    pub fn next_match(&mut self, forward: bool, inclusive: bool) -> bool {
+        let pattern = match self.search.as_ref() {
+            Some(p) => p,
+            None => return false,
+        };
+        let mut comments = Vec::new();
+        search_helper(self.comments.as_slice(), pattern, &mut self.plain, &mut comments);
+        if comments.is_empty() {
+            return false;
+        }
+        let n = comments.len();
+        let current = self.selected.and_then(|id| comments.iter().position(|(c, _)| *c == id));
+        let start = match (current, inclusive) {
+            (Some(i), true) => i,
+            (Some(i), false) if forward => (i + 1) % n,
+            (Some(i), false) => (i + n - 1) % n,
+            (None, _) if forward => 0,
+            (None, _) => n - 1,
+        };
+        let found = (0..n)
+            .map(|step| if forward { (start + step) % n } else { (start + n - step) % n })
+            .find(|i| comments[*i].1);
+        match found {
+            Some(i) => {
+                self.focus_comment(comments[i].0);
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn scroll_down(&mut self) {
-        self.scroll += 1
+        if self.scroll + 1 < self.lines.len() {
+            self.scroll += 1
+        }
+        self.select_top();
     }
     pub fn scroll_up(&mut self) {
         if self.scroll > 0 {
             self.scroll -= 1
         }
+        self.select_top();
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn comment(id: i64, author: &str, text: &str, replies: Vec<Comment>) -> Comment {
+        Comment {
+            id,
+            author: author.to_string(),
+            time: 0,
+            text: text.to_string(),
+            replies: Some(replies),
+            kids: Vec::new(),
+            collapsed: false,
+            pending: false,
+            item: Item::default(),
+        }
+    }
+
+    #[test]
+    fn finds_posted_replies() {
+        let posted = comment(-1, "alice", "See *this* at https://example.com/a&amp;b<p>Thanks", Vec::new());
+        let block = CommentBlock::from_comments(vec![
+            comment(1, "bob", "First", vec![
+                comment(3, "alice", "See <i>this</i> at <a href=\"https://example.com/a&amp;b\">https://example.com/a&amp;b</a><p>Thanks", Vec::new()),
+            ]),
+            comment(2, "alice", "Something else", Vec::new()),
+        ]);
+        // By id
+        assert!(block.has_reply(Some(1), Some(3), &posted));
+        assert!(!block.has_reply(Some(1), Some(4), &posted));
+        // By author and text, under the right parent only
+        assert!(block.has_reply(Some(1), None, &posted));
+        assert!(!block.has_reply(None, None, &posted));
+        assert!(!block.has_reply(Some(2), None, &posted));
+        assert!(!block.has_reply(Some(5), None, &posted));
+        let other = comment(-2, "carol", posted.text.as_str(), Vec::new());
+        assert!(!block.has_reply(Some(1), None, &other));
+    }
+
+    #[test]
+    fn wraps_words_and_cuts_long_ones() {
+        for &(text, width, rows) in [
+            ("", 10, &[][..]),
+            ("one two three", 7, &["one two", "three"][..]),
+            ("one two\n\nthree", 20, &["one two", "", "three"][..]),
+            ("see https://example.com/a/long/path here", 10, &["see", "https://ex", "ample.com/", "a/long/pat", "h here"][..]),
+            ("abcdef", 2, &["ab", "cd", "ef"][..]),
+            ("日本語のテキスト", 5, &["日本", "語の", "テキ", "スト"][..]),
+            ("word", 0, &["w", "o", "r", "d"][..]),
+        ].iter() {
+            assert_eq!(wrap(text, width), rows, "{:?} at {}", text, width);
+        }
+    }
+}
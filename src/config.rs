@@ -0,0 +1,228 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::export::ExportConfig;
+use crate::history::HistoryConfig;
+use crate::hn_api::NetworkConfig;
+use crate::inbox::NotificationConfig;
+use crate::reads::ReadingConfig;
+use crate::search::SearchConfig;
+use crate::session::AuthConfig;
+use crate::store::CacheConfig;
+use crate::story_list::ListConfig;
+use crate::tabs::TabsConfig;
+use crate::watch::WatchConfig;
+
+const CONFIG_DIR: &str = "hn_terminal";
+const CONFIG_FILE: &str = "config.toml";
+
+const MIN_PANE_PERCENT: u16 = 10;
+const MAX_PANE_PERCENT: u16 = 90;
+const PANE_STEP: u16 = 5;
+
+/// How the story list, the info box and the comments are arranged on a `StoryScreen`
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LayoutMode {
+    // List on top, info and comments stacked below
+    Vertical,
+    // List on the left, info and comments on the right
+    Horizontal,
+    // List hidden, info and comments take the whole screen
+    Reading,
+    // Only the focused pane is drawn
+    Zoom,
+}
+
+impl LayoutMode {
+    pub fn next(self) -> LayoutMode {
+        match self {
+            LayoutMode::Vertical => LayoutMode::Horizontal,
+            LayoutMode::Horizontal => LayoutMode::Reading,
+            LayoutMode::Reading => LayoutMode::Zoom,
+            LayoutMode::Zoom => LayoutMode::Vertical,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutConfig {
+    pub mode: LayoutMode,
+    // Percentage of the screen given to the story list
+    pub list_percent: u16,
+    // Percentage of the reading area given to the info box
+    pub info_percent: u16,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> LayoutConfig {
+        LayoutConfig {
+            mode: LayoutMode::Vertical,
+            list_percent: 30,
+            info_percent: 20,
+        }
+    }
+}
+
+impl LayoutConfig {
+    // Keeps hand edited sizes within what the keys can resize to, so no pane is left empty
+    fn clamp(&mut self) {
+        self.list_percent = self.list_percent.clamp(MIN_PANE_PERCENT, MAX_PANE_PERCENT);
+        self.info_percent = self.info_percent.clamp(MIN_PANE_PERCENT, MAX_PANE_PERCENT);
+    }
+
+    pub fn grow_list(&mut self) {
+        self.list_percent = (self.list_percent + PANE_STEP).min(MAX_PANE_PERCENT);
+    }
+    pub fn shrink_list(&mut self) {
+        self.list_percent = self.list_percent.saturating_sub(PANE_STEP).max(MIN_PANE_PERCENT);
+    }
+    pub fn grow_info(&mut self) {
+        self.info_percent = (self.info_percent + PANE_STEP).min(MAX_PANE_PERCENT);
+    }
+    pub fn shrink_info(&mut self) {
+        self.info_percent = self.info_percent.saturating_sub(PANE_STEP).max(MIN_PANE_PERCENT);
+    }
+}
+
+/// User settings, stored as TOML in the platform config directory
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub layout: LayoutConfig,
+    pub list: ListConfig,
+    pub tabs: TabsConfig,
+    pub export: ExportConfig,
+    pub auth: AuthConfig,
+    pub notifications: NotificationConfig,
+    pub watching: WatchConfig,
+    pub search: SearchConfig,
+    pub history: HistoryConfig,
+    pub reading: ReadingConfig,
+    pub network: NetworkConfig,
+    pub cache: CacheConfig,
+    /// Why the config file couldn't be used, if it couldn't. It isn't saved over then, so a
+    /// typo doesn't cost the rest of the settings
+    #[serde(skip)]
+    pub error: Option<String>,
+}
+
+impl Config {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join(CONFIG_DIR).join(CONFIG_FILE))
+    }
+
+    /// Loads the config file, falling back to the defaults if it is missing or invalid. What
+    /// was wrong with an invalid one is kept in `error`
+    pub fn load() -> Config {
+        let path = match Config::path() {
+            Some(p) => p,
+            None => return Config::default(),
+        };
+        let contents = match fs::read_to_string(&path) {
+            Ok(c) => c,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Config::default(),
+            Err(e) => return Config::invalid(&path, e.to_string()),
+        };
+        match toml::from_str::<Config>(contents.as_str()) {
+            Ok(mut config) => {
+                config.layout.clamp();
+                config
+            }
+            Err(e) => Config::invalid(&path, e.to_string()),
+        }
+    }
+
+    fn invalid(path: &Path, error: String) -> Config {
+        Config { error: Some(format!("{}: {}", path.display(), error)), ..Config::default() }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        if self.error.is_some() {
+            return Err(Error::new(ErrorKind::InvalidData, "Not saving over an invalid config file"));
+        }
+        let path = match Config::path() {
+            Some(p) => p,
+            None => return Err(Error::new(ErrorKind::NotFound, "Could not find config directory")),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = toml::to_string_pretty(self)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // The file can hold the HN password, so only the user may read it, also when an older
+        // version made it readable by everyone
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            if path.exists() {
+                fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        let mut file = options.open(&path)?;
+        file.write_all(contents.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+
+    // Every test shares the config file, so they take turns
+    static CONFIG_FILE: Mutex<()> = Mutex::new(());
+
+    // Keeps the config file of the tests out of the user's config directory, and writes
+    // `contents` to it
+    fn config_file(contents: &str) -> MutexGuard<'static, ()> {
+        let guard = CONFIG_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::set_var("XDG_CONFIG_HOME", std::env::temp_dir().join(format!("hn_terminal-test-{}", std::process::id())));
+        let path = Config::path().unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+        guard
+    }
+
+    #[test]
+    fn keeps_an_invalid_file() {
+        let contents = "[layout]\nlist_percent = 40\n[list\n";
+        let _file = config_file(contents);
+        let config = Config::load();
+        assert!(config.error.as_ref().unwrap().contains("config.toml"));
+        assert_eq!(config.layout.list_percent, LayoutConfig::default().list_percent);
+        assert!(config.save().is_err());
+        assert_eq!(fs::read_to_string(Config::path().unwrap()).unwrap(), contents);
+    }
+
+    #[test]
+    fn clamps_pane_sizes() {
+        let _file = config_file("[layout]\nlist_percent = 0\ninfo_percent = 100\n");
+        let config = Config::load();
+        assert_eq!(config.layout.list_percent, MIN_PANE_PERCENT);
+        assert_eq!(config.layout.info_percent, MAX_PANE_PERCENT);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn saves_a_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let _file = config_file("");
+        let path = Config::path().unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        let mut config = Config::load();
+        config.layout.list_percent = 40;
+        config.save().unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(Config::load().layout.list_percent, 40);
+        fs::remove_file(&path).unwrap();
+        Config::default().save().unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+    }
+}
@@ -0,0 +1,107 @@
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{Error, Write};
+use std::process::{self, Command};
+
+/// Opens `$VISUAL` or `$EDITOR` on a temporary file containing `initial`. Returns the edited
+/// text, or `None` when the file was left empty or unchanged
+pub fn edit(initial: &str) -> Result<Option<String>, Error> {
+    let editor = env::var("VISUAL")
+        .or_else(|_| env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let path = env::temp_dir().join(format!("hn_terminal-{}.txt", process::id()));
+    // Never reuse a file someone else put there, and keep the draft private
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(&path)?;
+    let written = file.write_all(initial.as_bytes());
+    drop(file);
+    if let Err(e) = written {
+        let _ = fs::remove_file(&path);
+        return Err(e);
+    }
+
+    // The editor setting may include arguments, e.g. `code --wait`
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or("vi");
+    let status = Command::new(program).args(words).arg(&path).status();
+    let text = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+
+    if !status?.success() {
+        return Err(Error::other(format!("{} exited with an error", program)));
+    }
+    let text = text?;
+    if text.trim().is_empty() || text == initial {
+        return Ok(None);
+    }
+    Ok(Some(text.trim_end().to_string()))
+}
+
+/// Parent text quoted with `> ` to pre-fill a reply
+pub fn quote(text: &str) -> String {
+    let mut quoted: Vec<String> = text.lines().map(|l| format!("> {}", l).trim_end().to_string()).collect();
+    quoted.push(String::new());
+    quoted.push(String::new());
+    quoted.join("\n")
+}
+
+/// What to post of the `reply` written under `quote`: the quote is left out when it wasn't
+/// touched, what was kept of an edited one stays. `None` when nothing else was written
+pub fn unquote(reply: &str, quote: &str) -> Option<String> {
+    let reply = match reply.strip_prefix(quote.trim_end()) {
+        Some(rest) => rest.trim(),
+        None => reply.trim(),
+    };
+    if reply.is_empty() { None } else { Some(reply.to_string()) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn drafts_in_a_new_private_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = env::temp_dir().join(format!("hn_terminal-editor-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        // Appends a line and notes the permissions the draft had
+        let script = dir.join("editor.sh");
+        fs::write(&script, format!("#!/bin/sh\nstat -c %a \"$1\" > {0}/mode\necho reply >> \"$1\"\n", dir.display())).unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o700)).unwrap();
+        env::set_var("VISUAL", script.as_os_str());
+
+        let initial = quote("quoted");
+        let edited = edit(initial.as_str()).unwrap().unwrap();
+        assert_eq!(edited, "> quoted\n\nreply");
+        assert_eq!(unquote(edited.as_str(), initial.as_str()).as_deref(), Some("reply"));
+        assert_eq!(fs::read_to_string(dir.join("mode")).unwrap().trim(), "600");
+
+        let path = env::temp_dir().join(format!("hn_terminal-{}.txt", process::id()));
+        fs::write(&path, "left behind").unwrap();
+        assert_eq!(edit("").unwrap_err().kind(), std::io::ErrorKind::AlreadyExists);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "left behind");
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn posts_only_quotes_that_were_edited() {
+        let initial = quote("first line\nsecond line");
+        for &(reply, posted) in [
+            ("> first line\n> second line\n\nreply", Some("reply")),
+            ("> first line\n> second line\n\n", None),
+            ("> second line\n\nreply", Some("> second line\n\nreply")),
+            ("> first line\nanswer\n> second line\n\nreply", Some("> first line\nanswer\n> second line\n\nreply")),
+            ("reply", Some("reply")),
+        ].iter() {
+            assert_eq!(unquote(reply, initial.as_str()).as_deref(), posted, "{:?}", reply);
+        }
+    }
+}
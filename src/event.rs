@@ -1,4 +1,5 @@
-use std::io;
+use std::collections::HashMap;
+use std::io::{self, Error, Read};
 use std::sync::mpsc;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
@@ -7,20 +8,70 @@ use std::sync::{
 use std::thread;
 use std::time::Duration;
 
-use termion::event::Key;
+use termion::event::{self as term_event, Key, MouseEvent};
 use termion::input::TermRead;
+use tui::layout::Rect;
+
+use crate::inbox::InboxPoll;
+use crate::store::Item;
 
 pub enum Event<I> {
     Input(I),
+    Mouse(MouseEvent),
     Tick,
+    // Items fetched on a worker thread, by id. Those that failed to load are empty
+    Loaded(HashMap<i64, Item>),
+    // A finished poll of the inbox
+    Inbox(Result<InboxPoll, Error>),
+    // A finished poll of the watched stories
+    Watched(Result<Vec<Item>, Error>),
+}
+
+/// Whether the 0-based terminal cell `(x, y)` falls inside `area`
+pub fn contains(area: Rect, x: u16, y: u16) -> bool {
+    x >= area.x && x < area.right() && y >= area.y && y < area.bottom()
+}
+
+const POLL_TIMEOUT_MS: i32 = 100;
+
+/// Reads stdin without buffering and stops reading while `paused` is set, so another
+/// program can be given the terminal. `idle` is set once reads have stopped
+struct PausableStdin {
+    paused: Arc<AtomicBool>,
+    idle: Arc<AtomicBool>,
+}
+
+impl Read for PausableStdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.paused.load(Ordering::SeqCst) {
+                self.idle.store(true, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(POLL_TIMEOUT_MS as u64));
+                continue;
+            }
+            self.idle.store(false, Ordering::SeqCst);
+            let mut fd = libc::pollfd { fd: libc::STDIN_FILENO, events: libc::POLLIN, revents: 0 };
+            if unsafe { libc::poll(&mut fd, 1, POLL_TIMEOUT_MS) } <= 0 {
+                continue;
+            }
+            let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) };
+            if n < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            return Ok(n as usize);
+        }
+    }
 }
 
-/// A small event handler that wrap termion input and tick events. Each event
+/// A small event handler that wrap termion input, mouse and tick events. Each event
 /// type is handled in its own thread and returned to a common `Receiver`
 pub struct Events {
     rx: mpsc::Receiver<Event<Key>>,
+    tx: mpsc::Sender<Event<Key>>,
     input_handle: thread::JoinHandle<()>,
     ignore_exit_key: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    idle: Arc<AtomicBool>,
     tick_handle: thread::JoinHandle<()>,
 }
 
@@ -47,22 +98,29 @@ impl Events {
     pub fn with_config(config: Config) -> Events {
         let (tx, rx) = mpsc::channel();
         let ignore_exit_key = Arc::new(AtomicBool::new(false));
+        let paused = Arc::new(AtomicBool::new(false));
+        let idle = Arc::new(AtomicBool::new(false));
         let input_handle = {
             let tx = tx.clone();
             let ignore_exit_key = ignore_exit_key.clone();
+            let stdin = PausableStdin { paused: paused.clone(), idle: idle.clone() };
             thread::spawn(move || {
-                let stdin = io::stdin();
-                for evt in stdin.keys() {
+                for evt in stdin.events().flatten() {
                     match evt {
-                        Ok(key) => {
-                            if let Err(_) = tx.send(Event::Input(key)) {
+                        term_event::Event::Key(key) => {
+                            if tx.send(Event::Input(key)).is_err() {
                                 return;
                             }
                             if !ignore_exit_key.load(Ordering::Relaxed) && key == config.exit_key {
                                 return;
                             }
                         }
-                        Err(_) => {}
+                        term_event::Event::Mouse(mouse) => {
+                            if tx.send(Event::Mouse(mouse)).is_err() {
+                                return;
+                            }
+                        }
+                        term_event::Event::Unsupported(_) => {}
                     }
                 }
             })
@@ -79,7 +137,10 @@ impl Events {
         };
         Events {
             rx,
+            tx,
             ignore_exit_key,
+            paused,
+            idle,
             input_handle,
             tick_handle,
         }
@@ -89,6 +150,16 @@ impl Events {
         self.rx.recv()
     }
 
+    /// Runs `job` on a thread of its own, its result arrives as an event
+    pub fn spawn<F>(&self, job: F)
+    where F: FnOnce() -> Event<Key> + Send + 'static {
+        let tx = self.tx.clone();
+        thread::spawn(move || {
+            // Gone only when the app is quitting
+            let _ = tx.send(job());
+        });
+    }
+
     pub fn disable_exit_key(&mut self) {
         self.ignore_exit_key.store(true, Ordering::Relaxed);
     }
@@ -96,4 +167,18 @@ impl Events {
     pub fn enable_exit_key(&mut self) {
         self.ignore_exit_key.store(false, Ordering::Relaxed);
     }
+
+    /// Stops reading input, returning once the input thread has let go of stdin
+    pub fn pause(&mut self) {
+        self.paused.store(true, Ordering::SeqCst);
+        while !self.idle.load(Ordering::SeqCst) {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    pub fn resume(&mut self) {
+        // Cleared first so a quick `pause` afterwards waits for the thread again
+        self.idle.store(false, Ordering::SeqCst);
+        self.paused.store(false, Ordering::SeqCst);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,146 @@
+use std::fs;
+use std::io::Error;
+use std::path::PathBuf;
+
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::comment_block::Comment;
+use crate::output::{self, date, int_field, str_field, Format};
+
+/// File formats a thread can be exported to
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Markdown,
+    Html,
+    Json,
+}
+
+impl ExportFormat {
+    pub fn parse(name: &str) -> Option<ExportFormat> {
+        match name.trim().to_lowercase().as_str() {
+            "markdown" | "md" | "m" => Some(ExportFormat::Markdown),
+            "html" | "h" => Some(ExportFormat::Html),
+            "json" | "j" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "markdown",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+            ExportFormat::Json => "json",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportConfig {
+    // Directory exports are written to, a leading `~` is the home directory
+    pub directory: String,
+    // File name without extension. `{id}`, `{title}`, `{author}` and `{date}` are replaced
+    pub filename: String,
+    pub format: ExportFormat,
+}
+
+impl Default for ExportConfig {
+    fn default() -> ExportConfig {
+        ExportConfig {
+            directory: "~/hn_exports".to_string(),
+            filename: "{date}-{id}-{title}".to_string(),
+            format: ExportFormat::Markdown,
+        }
+    }
+}
+
+// Lowercase words joined by dashes, safe to use in a file name
+fn slug(text: &str) -> String {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| w.to_lowercase())
+        .collect();
+    words.join("-")
+}
+
+fn directory(config: &ExportConfig) -> PathBuf {
+    match (config.directory.strip_prefix("~/"), dirs::home_dir()) {
+        (Some(rest), Some(home)) => home.join(rest),
+        _ => PathBuf::from(config.directory.as_str()),
+    }
+}
+
+fn file_name(config: &ExportConfig, item: &Map<String, Value>, format: ExportFormat) -> String {
+    let day = match Utc.timestamp_opt(int_field(item, "time"), 0).single() {
+        Some(d) => d.format("%Y-%m-%d").to_string(),
+        None => "".to_string(),
+    };
+    let name = config.filename
+        .replace("{id}", int_field(item, "id").to_string().as_str())
+        .replace("{title}", slug(str_field(item, "title")).as_str())
+        .replace("{author}", slug(str_field(item, "by")).as_str())
+        .replace("{date}", day.as_str());
+    format!("{}.{}", name, format.extension())
+}
+
+fn html_comment(c: &Comment, out: &mut String) {
+    out.push_str(format!(
+        "<div class=\"comment\" id=\"{}\">\n<p class=\"meta\"><b>{}</b> at {}</p>\n<div>{}</div>\n",
+        c.id, ammonia::clean_text(c.author.as_str()), date(c.time), ammonia::clean(c.text.as_str())
+    ).as_str());
+    if let Some(replies) = &c.replies {
+        for reply in replies {
+            html_comment(reply, out);
+        }
+    }
+    out.push_str("</div>\n");
+}
+
+/// A standalone HTML page for the story and its comments
+fn html(item: &Map<String, Value>, comments: &[Comment]) -> String {
+    let title = ammonia::clean_text(str_field(item, "title"));
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n\
+         <style>\nbody {{ background: #f6f6ef; font-family: Verdana, sans-serif; max-width: 60em; margin: auto; }}\n\
+         .meta {{ color: #828282; }}\n.comment {{ margin-left: 2em; }}\n</style>\n</head>\n<body>\n\
+         <h1><a href=\"{}\">{}</a></h1>\n<p class=\"meta\">{} points by {} at {} | {} comments</p>\n",
+        title, ammonia::clean_text(str_field(item, "url")), title, int_field(item, "score"),
+        ammonia::clean_text(str_field(item, "by")), date(int_field(item, "time")),
+        int_field(item, "descendants"));
+    let text = str_field(item, "text");
+    if !text.is_empty() {
+        out.push_str(format!("<div>{}</div>\n", ammonia::clean(text)).as_str());
+    }
+    for c in comments {
+        html_comment(c, &mut out);
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Writes the story `item` and its comment tree to the export directory, returning the file's path
+pub fn export(config: &ExportConfig, item: &Map<String, Value>, comments: &[Comment],
+              format: ExportFormat) -> Result<PathBuf, Error> {
+    let contents = match format {
+        ExportFormat::Markdown => output::thread(item, Some(comments), Format::Markdown),
+        ExportFormat::Json => output::thread(item, Some(comments), Format::Json),
+        ExportFormat::Html => html(item, comments),
+    };
+    let dir = directory(config);
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(file_name(config, item, format));
+    fs::write(&path, contents)?;
+    Ok(path)
+}
@@ -0,0 +1,251 @@
+use std::cmp::Reverse;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::output::{domain, int_field, str_field};
+use crate::store::Item;
+
+/// Orders a story list can be shown in
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum SortMode {
+    // The order of the feed
+    #[default]
+    Rank,
+    Points,
+    Comments,
+    Newest,
+    // Most comments per point, threads that are argued over more than they are upvoted
+    Controversial,
+}
+
+impl SortMode {
+    pub fn next(self) -> SortMode {
+        match self {
+            SortMode::Rank => SortMode::Points,
+            SortMode::Points => SortMode::Comments,
+            SortMode::Comments => SortMode::Newest,
+            SortMode::Newest => SortMode::Controversial,
+            SortMode::Controversial => SortMode::Rank,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SortMode::Rank => "rank",
+            SortMode::Points => "points",
+            SortMode::Comments => "comments",
+            SortMode::Newest => "newest",
+            SortMode::Controversial => "controversial",
+        }
+    }
+
+    /// Sorts `rows`, indices into `items`. Ties keep their feed order
+    pub fn sort(self, items: &[Item], rows: &mut [usize]) {
+        match self {
+            SortMode::Rank => rows.sort(),
+            SortMode::Points => rows.sort_by_key(|i| Reverse(int_field(&items[*i], "score"))),
+            SortMode::Comments => rows.sort_by_key(|i| Reverse(int_field(&items[*i], "descendants"))),
+            SortMode::Newest => rows.sort_by_key(|i| Reverse(int_field(&items[*i], "time"))),
+            SortMode::Controversial => {
+                let ratio = |i: &usize| int_field(&items[*i], "descendants") as f64 / int_field(&items[*i], "score").max(1) as f64;
+                rows.sort_by(|a, b| ratio(b).total_cmp(&ratio(a)));
+            }
+        }
+    }
+}
+
+/// Kinds of item the filter can be limited to
+const KINDS: [&str; 5] = ["story", "ask", "show", "job", "poll"];
+
+// `ask` and `show` are stories with an `Ask HN:` or `Show HN:` title
+fn kind(item: &Item) -> &'static str {
+    let title = str_field(item, "title");
+    match str_field(item, "type") {
+        "job" => "job",
+        "poll" => "poll",
+        _ if title.starts_with("Ask HN") => "ask",
+        _ if title.starts_with("Show HN") => "show",
+        _ => "story",
+    }
+}
+
+// `90m`, `6h` or `2d` in seconds, a bare number counts hours
+fn parse_age(value: &str) -> Option<i64> {
+    let (number, unit) = match value.char_indices().last()? {
+        (i, 'm') => (&value[..i], 60),
+        (i, 'h') => (&value[..i], 60 * 60),
+        (i, 'd') => (&value[..i], 60 * 60 * 24),
+        _ => (value, 60 * 60),
+    };
+    number.parse::<i64>().ok().map(|n| n * unit)
+}
+
+fn format_age(seconds: i64) -> String {
+    match seconds {
+        s if s % (60 * 60 * 24) == 0 => format!("{}d", s / (60 * 60 * 24)),
+        s if s % (60 * 60) == 0 => format!("{}h", s / (60 * 60)),
+        s => format!("{}m", s / 60),
+    }
+}
+
+/// Which stories a list shows, written in the filter bar as space separated terms such as
+/// `points:100 comments:10 age:6h domain:github.com -domain:medium.com type:ask`
+#[derive(Clone, Default, PartialEq)]
+pub struct ListFilter {
+    pub min_points: i64,
+    pub min_comments: i64,
+    // In seconds
+    pub max_age: Option<i64>,
+    // A story has to be from one of these, when there are any
+    pub domains: Vec<String>,
+    pub excluded_domains: Vec<String>,
+    pub kind: Option<String>,
+}
+
+impl ListFilter {
+    pub fn parse(input: &str) -> Result<ListFilter, String> {
+        let mut filter = ListFilter::default();
+        for term in input.split_whitespace() {
+            let (key, value) = match term.split_once(':') {
+                Some((key, value)) if !value.is_empty() => (key, value),
+                _ => return Err(format!("expected key:value, got {}", term)),
+            };
+            let number = || value.parse::<i64>().map_err(|_| format!("{} is not a number", value));
+            match key {
+                "points" => filter.min_points = number()?,
+                "comments" => filter.min_comments = number()?,
+                "age" => filter.max_age = Some(parse_age(value).ok_or(format!("{} is not an age like 6h", value))?),
+                "domain" => filter.domains.push(value.to_lowercase()),
+                "-domain" => filter.excluded_domains.push(value.to_lowercase()),
+                "type" if KINDS.contains(&value) => filter.kind = Some(value.to_string()),
+                "type" => return Err(format!("type is one of {}", KINDS.join(", "))),
+                _ => return Err(format!("unknown filter {}", key)),
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == ListFilter::default()
+    }
+
+    /// The filter the way `parse` reads it
+    pub fn describe(&self) -> String {
+        let mut terms = Vec::new();
+        if self.min_points > 0 {
+            terms.push(format!("points:{}", self.min_points));
+        }
+        if self.min_comments > 0 {
+            terms.push(format!("comments:{}", self.min_comments));
+        }
+        if let Some(age) = self.max_age {
+            terms.push(format!("age:{}", format_age(age)));
+        }
+        terms.extend(self.domains.iter().map(|d| format!("domain:{}", d)));
+        terms.extend(self.excluded_domains.iter().map(|d| format!("-domain:{}", d)));
+        if let Some(kind) = self.kind.as_ref() {
+            terms.push(format!("type:{}", kind));
+        }
+        terms.join(" ")
+    }
+
+    pub fn matches(&self, item: &Item) -> bool {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        // Subdomains count, `domain:github.com` also matches gist.github.com
+        let from = |domains: &[String]| domain(str_field(item, "url"))
+            .is_some_and(|d| domains.iter().any(|wanted| d == *wanted || d.ends_with(format!(".{}", wanted).as_str())));
+        int_field(item, "score") >= self.min_points
+            && int_field(item, "descendants") >= self.min_comments
+            && self.max_age.is_none_or(|age| now - int_field(item, "time") <= age)
+            && (self.domains.is_empty() || from(&self.domains))
+            && !from(&self.excluded_domains)
+            && self.kind.as_ref().is_none_or(|k| k == kind(item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn item(value: serde_json::Value) -> Item {
+        Arc::new(value.as_object().unwrap().clone())
+    }
+
+    #[test]
+    fn parses_and_describes_filters() {
+        for &(input, described) in [
+            ("", ""),
+            ("points:100 comments:10", "points:100 comments:10"),
+            ("age:90m", "age:90m"),
+            ("age:6h", "age:6h"),
+            ("age:2d", "age:2d"),
+            // A bare number counts hours, whole days are shown as such
+            ("age:3", "age:3h"),
+            ("age:48h", "age:2d"),
+            ("age:120m", "age:2h"),
+            ("domain:GitHub.com -domain:medium.com", "domain:github.com -domain:medium.com"),
+            ("type:ask   points:5", "points:5 type:ask"),
+        ].iter() {
+            let filter = ListFilter::parse(input).unwrap();
+            assert_eq!(filter.describe(), described, "{:?}", input);
+            assert!(ListFilter::parse(described).unwrap() == filter, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_terms() {
+        for &(input, error) in [
+            ("points", "expected key:value, got points"),
+            ("points:", "expected key:value, got points:"),
+            ("points:lots", "lots is not a number"),
+            ("comments:1.5", "1.5 is not a number"),
+            ("age:6w", "6w is not an age like 6h"),
+            ("age:h", "h is not an age like 6h"),
+            ("type:link", "type is one of story, ask, show, job, poll"),
+            ("color:red", "unknown filter color"),
+        ].iter() {
+            assert_eq!(ListFilter::parse(input).err().as_deref(), Some(error), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn matches_stories() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64;
+        let story = item(serde_json::json!({
+            "type": "story", "title": "Show HN: A thing", "url": "https://gist.github.com/a/b",
+            "score": 50, "descendants": 5, "time": now - 2 * 60 * 60,
+        }));
+        for &(input, matches) in [
+            ("", true),
+            ("points:50 comments:5", true),
+            ("points:51", false),
+            ("comments:6", false),
+            ("age:3h", true),
+            ("age:90m", false),
+            ("domain:github.com", true),
+            ("domain:gist.github.com", true),
+            ("domain:hub.com", false),
+            ("domain:gitlab.com domain:github.com", true),
+            ("-domain:github.com", false),
+            ("-domain:medium.com", true),
+            ("type:show", true),
+            ("type:story", false),
+        ].iter() {
+            assert_eq!(ListFilter::parse(input).unwrap().matches(&story), matches, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn sorts_by_controversy() {
+        let items: Vec<Item> = [(10, 20), (0, 0), (0, 5), (1, 2), (4, 1)].iter()
+            .map(|(score, comments)| item(serde_json::json!({ "score": score, "descendants": comments })))
+            .collect();
+        let mut rows: Vec<usize> = (0..items.len()).collect();
+        SortMode::Controversial.sort(&items, &mut rows);
+        // Stories without points count as having one, ties keep their order
+        assert_eq!(rows, [2, 0, 3, 4, 1]);
+    }
+}
@@ -0,0 +1,352 @@
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Constraint, Rect};
+use tui::style::Modifier;
+use tui::widgets::{Block, Borders, BorderType, Row, Table, TableState};
+
+use crate::colors::{get_style, HNStyles};
+use crate::comment_block::Comment;
+use crate::output::plain_text;
+
+const COLUMNS: [&str; 6] = ["Company", "Role", "Location", "Remote", "Salary", "Visa"];
+const WIDTHS: [Constraint; 6] = [
+    Constraint::Percentage(20),
+    Constraint::Percentage(25),
+    Constraint::Percentage(20),
+    Constraint::Percentage(10),
+    Constraint::Percentage(15),
+    Constraint::Percentage(10),
+];
+const REMOTE_WORDS: [&str; 1] = ["remote"];
+const ONSITE_WORDS: [&str; 4] = ["onsite", "on-site", "on site", "in office"];
+const JOB_TYPES: [&str; 6] = ["full-time", "full time", "part-time", "part time", "contract", "intern"];
+// Ways posts say they won't sponsor a visa
+const NO_VISA: [&str; 4] = ["no visa", "not sponsor", "cannot sponsor", "unable to sponsor"];
+
+/// Columns the table can be sorted by
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    Company,
+    Role,
+    Location,
+    Remote,
+    Salary,
+}
+
+impl Column {
+    fn next(self) -> Column {
+        match self {
+            Column::Company => Column::Role,
+            Column::Role => Column::Location,
+            Column::Location => Column::Remote,
+            Column::Remote => Column::Salary,
+            Column::Salary => Column::Company,
+        }
+    }
+
+    fn index(self) -> usize {
+        match self {
+            Column::Company => 0,
+            Column::Role => 1,
+            Column::Location => 2,
+            Column::Remote => 3,
+            Column::Salary => 4,
+        }
+    }
+}
+
+/// A top level comment of a hiring thread, split up from its `Company | Role | Location | ...` header
+pub struct Job {
+    pub id: i64,
+    pub company: String,
+    pub role: String,
+    pub location: String,
+    pub remote: bool,
+    pub onsite: bool,
+    pub visa: bool,
+    pub salary: String,
+    // Whole post in lowercase, for keyword filters
+    text: String,
+}
+
+fn contains_any(text: &str, words: &[&str]) -> bool {
+    words.iter().any(|w| text.contains(w))
+}
+
+// `$150k`, `€60,000` or `120k-160k`
+fn looks_like_salary(field: &str) -> bool {
+    if field.contains(['$', '€', '£']) {
+        return true;
+    }
+    let chars: Vec<char> = field.to_lowercase().chars().collect();
+    chars.windows(2).any(|w| w[0].is_ascii_digit() && w[1] == 'k')
+}
+
+// First number in a salary, in thousands, to sort by
+fn salary_amount(salary: &str) -> u64 {
+    let digits: String = salary
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit() || *c == ',')
+        .filter(|c| c.is_ascii_digit())
+        .collect();
+    let amount = digits.parse::<u64>().unwrap_or(0);
+    if amount >= 1000 { amount / 1000 } else { amount }
+}
+
+impl Job {
+    /// Parses the header line of a post, `None` when it doesn't use the `|` separated format
+    pub fn parse(comment: &Comment) -> Option<Job> {
+        let text = plain_text(comment.text.as_str());
+        let header = text.lines().find(|l| !l.trim().is_empty())?;
+        if !header.contains('|') {
+            return None;
+        }
+        let fields: Vec<&str> = header.split('|').map(|f| f.trim()).filter(|f| !f.is_empty()).collect();
+        let lower = text.to_lowercase();
+        let mut job = Job {
+            id: comment.id,
+            company: fields.first()?.to_string(),
+            role: String::new(),
+            location: String::new(),
+            remote: false,
+            onsite: false,
+            visa: lower.contains("visa") && !contains_any(lower.as_str(), &NO_VISA),
+            salary: String::new(),
+            text: lower,
+        };
+        for field in &fields[1..] {
+            let f = field.to_lowercase();
+            let remote = contains_any(f.as_str(), &REMOTE_WORDS);
+            let onsite = contains_any(f.as_str(), &ONSITE_WORDS);
+            job.remote |= remote;
+            job.onsite |= onsite;
+            // A field that is only "REMOTE" or "ONSITE" says nothing else
+            let bare = f.split(|c: char| !c.is_alphanumeric() && c != '-')
+                .all(|w| w.is_empty() || REMOTE_WORDS.contains(&w) || ONSITE_WORDS.contains(&w) || w == "or");
+            if looks_like_salary(field) {
+                if job.salary.is_empty() {
+                    job.salary = field.to_string();
+                }
+            } else if bare || f.contains("://") || f.starts_with("www.") || contains_any(f.as_str(), &JOB_TYPES) {
+                continue;
+            } else if job.role.is_empty() && !remote && !onsite {
+                job.role = field.to_string();
+            } else if job.location.is_empty() {
+                job.location = field.to_string();
+            }
+        }
+        Some(job)
+    }
+
+    fn cell(&self, column: usize) -> String {
+        let flag = |b: bool| if b { "yes" } else { "" }.to_string();
+        match column {
+            0 => self.company.clone(),
+            1 => self.role.clone(),
+            2 => self.location.clone(),
+            3 => if self.remote && self.onsite {
+                "hybrid".to_string()
+            } else {
+                flag(self.remote)
+            },
+            4 => self.salary.clone(),
+            _ => flag(self.visa),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct HiringFilter {
+    pub remote: bool,
+    pub onsite: bool,
+    pub visa: bool,
+    pub location: String,
+    pub keyword: String,
+}
+
+impl HiringFilter {
+    fn matches(&self, job: &Job) -> bool {
+        (!self.remote || job.remote)
+            && (!self.onsite || job.onsite)
+            && (!self.visa || job.visa)
+            && job.location.to_lowercase().contains(self.location.to_lowercase().as_str())
+            && job.text.contains(self.keyword.to_lowercase().as_str())
+    }
+
+    fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if self.remote {
+            parts.push("remote".to_string());
+        }
+        if self.onsite {
+            parts.push("onsite".to_string());
+        }
+        if self.visa {
+            parts.push("visa".to_string());
+        }
+        if !self.location.is_empty() {
+            parts.push(format!("location \"{}\"", self.location));
+        }
+        if !self.keyword.is_empty() {
+            parts.push(format!("keyword \"{}\"", self.keyword));
+        }
+        parts.join(", ")
+    }
+}
+
+/// Table of the jobs posted in a "Who is hiring?" thread
+pub struct HiringView {
+    jobs: Vec<Job>,
+    // Indices into `jobs` that pass the filter, in sorted order
+    rows: Vec<usize>,
+    state: TableState,
+    sort: Column,
+    pub filter: HiringFilter,
+}
+
+impl HiringView {
+    /// Whether a story is one of the monthly hiring threads
+    pub fn is_hiring_thread(title: &str) -> bool {
+        title.to_lowercase().contains("who is hiring")
+    }
+
+    pub fn new(comments: &[Comment]) -> HiringView {
+        let mut view = HiringView {
+            jobs: comments.iter().filter_map(Job::parse).collect(),
+            rows: Vec::new(),
+            state: TableState::default(),
+            sort: Column::Company,
+            filter: HiringFilter::default(),
+        };
+        view.apply();
+        view
+    }
+
+    /// Filters and sorts the rows again, keeping the selected job when it is still shown
+    pub fn apply(&mut self) {
+        let selected = self.selected();
+        let jobs = &self.jobs;
+        let mut rows: Vec<usize> = (0..jobs.len()).filter(|i| self.filter.matches(&jobs[*i])).collect();
+        match self.sort {
+            // Remote jobs and the best paid first
+            Column::Remote => rows.sort_by_key(|i| !jobs[*i].remote),
+            Column::Salary => rows.sort_by_key(|i| std::cmp::Reverse(salary_amount(jobs[*i].salary.as_str()))),
+            column => rows.sort_by_key(|i| jobs[*i].cell(column.index()).to_lowercase()),
+        }
+        self.rows = rows;
+        let index = selected
+            .and_then(|id| self.rows.iter().position(|i| self.jobs[*i].id == id))
+            .or(if self.rows.is_empty() { None } else { Some(0) });
+        self.state.select(index);
+    }
+
+    /// Id of the comment of the selected job
+    pub fn selected(&self) -> Option<i64> {
+        self.state.selected().and_then(|i| self.rows.get(i)).map(|i| self.jobs[*i].id)
+    }
+
+    pub fn next(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(self.rows.len().saturating_sub(1))));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn cycle_sort(&mut self) {
+        self.sort = self.sort.next();
+        self.apply();
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let mut title = format!(" Who is hiring - {} of {} jobs, sorted by {} ",
+                                self.rows.len(), self.jobs.len(), COLUMNS[self.sort.index()]);
+        let filters = self.filter.describe();
+        if !filters.is_empty() {
+            title = format!("{}- {} ", title, filters);
+        }
+        let header: Vec<String> = COLUMNS.iter().enumerate()
+            .map(|(i, c)| if i == self.sort.index() { format!("{} ▼", c) } else { c.to_string() })
+            .collect();
+        let jobs = &self.jobs;
+        let rows = self.rows.iter()
+            .map(|i| Row::Data((0..COLUMNS.len()).map(move |c| jobs[*i].cell(c))));
+        let table = Table::new(header.iter(), rows)
+            .block(Block::default()
+                .title(title.as_str())
+                .title_style(get_style(HNStyles::WhiteTitle))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Double)
+                .border_style(get_style(HNStyles::OrangeBorder)))
+            .header_style(get_style(HNStyles::WhiteTitle).modifier(Modifier::BOLD))
+            .widths(&WIDTHS)
+            .style(get_style(HNStyles::WhiteBlock))
+            .highlight_style(get_style(HNStyles::OrangeTitle))
+            .column_spacing(1);
+        f.render_stateful_widget(table, area, &mut self.state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Item;
+
+    fn post(text: &str) -> Comment {
+        Comment {
+            id: 1,
+            author: "a".to_string(),
+            time: 0,
+            text: text.to_string(),
+            replies: None,
+            kids: Vec::new(),
+            collapsed: false,
+            pending: false,
+            item: Item::default(),
+        }
+    }
+
+    #[test]
+    fn parses_headers() {
+        // Post, then company, role, location, remote, onsite, visa and salary
+        let cases = [
+            ("Acme | Backend Engineer | Berlin | ONSITE | €70k-90k",
+             Some(("Acme", "Backend Engineer", "Berlin", false, true, false, "€70k-90k"))),
+            ("Acme | Rust Developer | REMOTE (US)",
+             Some(("Acme", "Rust Developer", "REMOTE (US)", true, false, false, ""))),
+            ("Acme | Designer | London | REMOTE or ONSITE",
+             Some(("Acme", "Designer", "London", true, true, false, ""))),
+            ("Acme | https://acme.example | Full-time | SRE | Paris | 120k-160k",
+             Some(("Acme", "SRE", "Paris", false, false, false, "120k-160k"))),
+            ("Acme | SRE | NYC | $150,000 - $180,000 | Visa sponsorship available",
+             Some(("Acme", "SRE", "NYC", false, false, true, "$150,000 - $180,000"))),
+            ("Acme | SRE | NYC<p>No visa sponsorship, sorry.",
+             Some(("Acme", "SRE", "NYC", false, false, false, ""))),
+            ("Acme | SRE<p>We cannot sponsor a visa.",
+             Some(("Acme", "SRE", "", false, false, false, ""))),
+            ("Acme | ",
+             Some(("Acme", "", "", false, false, false, ""))),
+            ("Acme is hiring engineers in Berlin, visa sponsorship available", None),
+            ("<p>", None),
+        ];
+        for (text, expected) in cases.iter() {
+            let job = Job::parse(&post(text));
+            let parsed = job.as_ref().map(|j| {
+                (j.company.as_str(), j.role.as_str(), j.location.as_str(), j.remote, j.onsite, j.visa, j.salary.as_str())
+            });
+            assert_eq!(parsed, *expected, "{}", text);
+        }
+    }
+
+    #[test]
+    fn reads_salary_amounts() {
+        for (salary, amount) in [("$150k", 150), ("€60,000", 60), ("120k-160k", 120), ("competitive", 0)] {
+            assert_eq!(salary_amount(salary), amount, "{}", salary);
+        }
+    }
+}
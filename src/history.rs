@@ -0,0 +1,288 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::Rect;
+use tui::style::Modifier;
+use tui::widgets::{Block, Borders, BorderType, List, ListState, Text};
+
+use crate::colors::{get_style, HNStyles};
+use crate::output::{date, int_field, plain_text, str_field};
+
+const HISTORY_DIR: &str = "hn_terminal";
+const HISTORY_FILE: &str = "history.db";
+const MAX_RESULTS: usize = 200;
+
+const SCHEMA: &str = "
+    PRAGMA journal_mode = WAL;
+    PRAGMA synchronous = NORMAL;
+    CREATE TABLE IF NOT EXISTS items (
+        id INTEGER PRIMARY KEY,
+        type TEXT NOT NULL,
+        time INTEGER NOT NULL,
+        json TEXT NOT NULL
+    );
+    CREATE VIRTUAL TABLE IF NOT EXISTS item_index USING fts5(title, text, by);
+";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HistoryConfig {
+    // Keep a searchable copy of every story and comment that is loaded
+    pub enabled: bool,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> HistoryConfig {
+        HistoryConfig {
+            enabled: true,
+        }
+    }
+}
+
+// Opened by `init`, stays `None` when the history is off or the database can't be opened
+static INDEX: OnceLock<Mutex<Option<Connection>>> = OnceLock::new();
+
+fn open() -> Result<Connection, Error> {
+    let dir = match dirs::data_dir() {
+        Some(d) => d.join(HISTORY_DIR),
+        None => return Err(Error::new(ErrorKind::NotFound, "Could not find data directory")),
+    };
+    fs::create_dir_all(&dir)?;
+    let connection = Connection::open(dir.join(HISTORY_FILE)).map_err(db_error)?;
+    connection.execute_batch(SCHEMA).map_err(db_error)?;
+    Ok(connection)
+}
+
+fn db_error(e: rusqlite::Error) -> Error {
+    Error::other(format!("History database: {}", e))
+}
+
+/// Opens the history database when it is enabled. Loading items works the same without it
+pub fn init(config: &HistoryConfig) {
+    let connection = if config.enabled { open().ok() } else { None };
+    let _ = INDEX.set(Mutex::new(connection));
+}
+
+fn with_index<T>(f: impl FnOnce(&Connection) -> Result<T, rusqlite::Error>) -> Result<T, Error> {
+    let index = INDEX.get().and_then(|i| i.lock().ok());
+    match index.as_ref().and_then(|i| i.as_ref()) {
+        Some(connection) => f(connection).map_err(db_error),
+        None => Err(Error::new(ErrorKind::NotFound, "The history is turned off")),
+    }
+}
+
+/// Adds stories and comments fresh from the API to the index, replacing older copies
+pub fn record(items: &[Map<String, Value>]) {
+    // The history is a convenience, failing to write it mustn't stop items from loading
+    let _ = with_index(|connection| {
+        let tx = connection.unchecked_transaction()?;
+        for item in items.iter().filter(|i| i.contains_key("id") && !i.contains_key("deleted")) {
+            let id = int_field(item, "id");
+            tx.execute("INSERT OR REPLACE INTO items (id, type, time, json) VALUES (?1, ?2, ?3, ?4)",
+                       params![id, str_field(item, "type"), int_field(item, "time"), Value::from(item.clone()).to_string()])?;
+            tx.execute("DELETE FROM item_index WHERE rowid = ?1", params![id])?;
+            tx.execute("INSERT INTO item_index (rowid, title, text, by) VALUES (?1, ?2, ?3, ?4)",
+                       params![id, str_field(item, "title"), plain_text(str_field(item, "text")), str_field(item, "by")])?;
+        }
+        tx.commit()
+    });
+}
+
+/// The stored copy of item `id`
+pub fn cached(id: i64) -> Option<Map<String, Value>> {
+    let json: String = with_index(|connection| {
+        connection.query_row("SELECT json FROM items WHERE id = ?1", params![id], |row| row.get(0)).optional()
+    }).ok()??;
+    serde_json::from_str(json.as_str()).ok()
+}
+
+/// Follows the `parent` links of the stored item `id` up to its story, like `get_root`
+/// but only using stored items
+pub fn cached_root(id: i64) -> Option<(Map<String, Value>, Option<i64>)> {
+    let mut item = cached(id)?;
+    let comment = if str_field(&item, "type") == "comment" { Some(id) } else { None };
+    while matches!(str_field(&item, "type"), "comment" | "pollopt") {
+        item = cached(item.get("parent")?.as_i64()?)?;
+    }
+    Some((item, comment))
+}
+
+// Each word of the query is quoted, so FTS operators typed by accident don't cause errors
+fn match_query(query: &str) -> String {
+    query.split_whitespace()
+        .map(|w| format!("\"{}\"", w.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Periods the results can be limited to
+#[derive(Clone, Copy, PartialEq)]
+pub enum DateRange {
+    Any,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl DateRange {
+    fn next(self) -> DateRange {
+        match self {
+            DateRange::Any => DateRange::Day,
+            DateRange::Day => DateRange::Week,
+            DateRange::Week => DateRange::Month,
+            DateRange::Month => DateRange::Year,
+            DateRange::Year => DateRange::Any,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            DateRange::Any => "any time",
+            DateRange::Day => "past day",
+            DateRange::Week => "past week",
+            DateRange::Month => "past month",
+            DateRange::Year => "past year",
+        }
+    }
+
+    // Earliest item time included
+    fn since(self) -> i64 {
+        let days = match self {
+            DateRange::Any => return 0,
+            DateRange::Day => 1,
+            DateRange::Week => 7,
+            DateRange::Month => 30,
+            DateRange::Year => 365,
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        now - days * 24 * 60 * 60
+    }
+}
+
+pub struct HistoryResult {
+    pub id: i64,
+    title: String,
+    by: String,
+    time: i64,
+    // Part of the text around the match
+    snippet: String,
+}
+
+/// Best matches for `query` among the stored items posted after `since`
+pub fn search(query: &str, since: i64) -> Result<Vec<HistoryResult>, Error> {
+    let query = match_query(query);
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    with_index(|connection| {
+        let mut statement = connection.prepare(
+            "SELECT items.id, item_index.title, item_index.by, items.time,
+                    snippet(item_index, 1, '', '', '…', 16)
+             FROM item_index JOIN items ON items.id = item_index.rowid
+             WHERE item_index MATCH ?1 AND items.time >= ?2
+             ORDER BY bm25(item_index) LIMIT ?3")?;
+        let rows = statement.query_map(params![query, since, MAX_RESULTS as i64], |row| {
+            Ok(HistoryResult {
+                id: row.get(0)?,
+                title: row.get(1)?,
+                by: row.get(2)?,
+                time: row.get(3)?,
+                snippet: row.get(4)?,
+            })
+        })?;
+        rows.collect()
+    })
+}
+
+/// Search screen over everything read so far, drawn over the current tab
+pub struct HistoryView {
+    pub query: String,
+    range: DateRange,
+    results: Vec<HistoryResult>,
+    state: ListState,
+    // Why the last search failed
+    error: Option<String>,
+}
+
+impl HistoryView {
+    pub fn new() -> HistoryView {
+        HistoryView {
+            query: String::new(),
+            range: DateRange::Any,
+            results: Vec::new(),
+            state: ListState::default(),
+            error: None,
+        }
+    }
+
+    /// Runs the search again for the current query and date range
+    pub fn refresh(&mut self) {
+        match search(self.query.as_str(), self.range.since()) {
+            Ok(results) => {
+                self.results = results;
+                self.error = None;
+            }
+            Err(e) => {
+                self.results = Vec::new();
+                self.error = Some(e.to_string());
+            }
+        }
+        self.state.select(if self.results.is_empty() { None } else { Some(0) });
+    }
+
+    pub fn cycle_range(&mut self) {
+        self.range = self.range.next();
+        self.refresh();
+    }
+
+    /// Id of the selected result
+    pub fn selected(&self) -> Option<i64> {
+        self.state.selected().and_then(|i| self.results.get(i)).map(|r| r.id)
+    }
+
+    pub fn next(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(self.results.len().saturating_sub(1))));
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let title = match self.error.as_ref() {
+            Some(e) => format!(" History - {} ", e),
+            None if self.query.is_empty() => format!(" History - press / to search, {} ", self.range.name()),
+            None => format!(" History - {} results for \"{}\", {} ", self.results.len(), self.query, self.range.name()),
+        };
+        let rows = self.results.iter().map(|r| {
+            let text = if r.title.is_empty() { r.snippet.as_str() } else { r.title.as_str() };
+            Text::raw(format!("{} {}: {}", date(r.time), r.by, text.split_whitespace().collect::<Vec<&str>>().join(" ")))
+        });
+        let list = List::new(rows)
+            .block(Block::default()
+                .title(title.as_str())
+                .title_style(get_style(HNStyles::WhiteTitle))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Double)
+                .border_style(get_style(HNStyles::OrangeBorder)))
+            .style(get_style(HNStyles::WhiteBlock))
+            .highlight_style(get_style(HNStyles::WhiteBlock).modifier(Modifier::BOLD))
+            .highlight_symbol(">>");
+        f.render_stateful_widget(list, area, &mut self.state);
+    }
+}
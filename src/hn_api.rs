@@ -1,13 +1,23 @@
+use std::collections::HashMap;
 use std::io::{Error, ErrorKind};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use async_recursion::async_recursion;
+use chrono::NaiveDate;
 use futures::future::join_all;
-use reqwest::blocking;
-use reqwest::Client;
-use serde_json::{Map, Number, Value};
+use reqwest::{Client, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use tokio::runtime::Runtime;
+use tokio::time::delay_for;
 use crate::comment_block::Comment;
+use crate::output::str_field;
+use crate::store::{self, Item};
 
 const URI_PREFIX: &str = "https://hacker-news.firebaseio.com/v0/";
+const URI_SEARCH_PREFIX: &str = "https://hn.algolia.com/api/v1/";
+// Failures kept for the UI between two `take_failures` calls
+const MAX_FAILURES: usize = 100;
 
 const URI_TOP_STORIES: &str = "topstories";
 const URI_NEW_STORIES: &str = "newstories";
@@ -17,14 +27,266 @@ const URI_SHOW_STORIES: &str = "showstories";
 const URI_JOB_STORIES: &str = "jobstories";
 
 const URI_ITEM: &str = "item/";
+const URI_USER: &str = "user/";
 
+const URI_SEARCH: &str = "search";
+const URI_SEARCH_BY_DATE: &str = "search_by_date";
+// Most stories a search feed lists
+const SEARCH_HITS: usize = 200;
+// How front page days are written
+const DAY_FORMAT: &str = "%Y-%m-%d";
+
+/// Where the stories of a tab come from, one of HN's feeds or a search
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
 pub enum ListType {
+    #[serde(rename = "top")]
     TopStories,
+    #[serde(rename = "new")]
     NewStories,
+    #[serde(rename = "best")]
     BestStories,
+    #[serde(rename = "ask")]
     AskStories,
+    #[serde(rename = "show")]
     ShowStories,
+    #[serde(rename = "job")]
     JobStories,
+    // Stories matching an Algolia query, `numeric_filters` such as `points>50,num_comments>10`
+    Search {
+        query: String,
+        #[serde(default, skip_serializing_if = "String::is_empty")]
+        numeric_filters: String,
+    },
+    // Stories submitted by a user, newest first
+    User { name: String },
+    // Stories that made the front page on a day, written as `2026-10-01`
+    FrontPage { date: String },
+}
+
+impl ListType {
+    /// Title of a tab showing this feed
+    pub fn title(&self) -> String {
+        match self {
+            ListType::TopStories => "Top Stories".to_string(),
+            ListType::NewStories => "New Stories".to_string(),
+            ListType::BestStories => "Best Stories".to_string(),
+            ListType::AskStories => "Ask Stories".to_string(),
+            ListType::ShowStories => "Show Stories".to_string(),
+            ListType::JobStories => "Job Stories".to_string(),
+            ListType::Search { query, numeric_filters } if numeric_filters.is_empty() => query.clone(),
+            ListType::Search { query, numeric_filters } => format!("{} {}", query, numeric_filters).trim().to_string(),
+            ListType::User { name } => format!("by {}", name),
+            ListType::FrontPage { date } => format!("Front page {}", date),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    // Where the API lives, e.g. a local server for testing
+    pub api_url: String,
+    // Where the Algolia search API lives, used by search, user and front page feeds
+    pub search_url: String,
+    // Seconds a single request may take, including reading the response
+    pub timeout_secs: u64,
+    // Extra attempts made after timeouts, connection errors and 5xx or 429 responses
+    pub retries: u32,
+    // Wait before the first retry, doubled for every one after it
+    pub backoff_ms: u64,
+    // Most requests started per second, 0 for no limit
+    pub requests_per_second: u32,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> NetworkConfig {
+        NetworkConfig {
+            api_url: URI_PREFIX.to_string(),
+            search_url: URI_SEARCH_PREFIX.to_string(),
+            timeout_secs: 10,
+            retries: 3,
+            backoff_ms: 250,
+            requests_per_second: 50,
+        }
+    }
+}
+
+// Set by `init`, the defaults are used until then
+static NETWORK: OnceLock<NetworkConfig> = OnceLock::new();
+// When the next request may start under `requests_per_second`
+static NEXT_REQUEST: Mutex<Option<Instant>> = Mutex::new(None);
+// Requests that still failed after all their retries
+static FAILURES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Sets the timeouts, retries and rate limit used for every request
+pub fn init(config: &NetworkConfig) {
+    let mut config = config.clone();
+    for url in [&mut config.api_url, &mut config.search_url] {
+        if !url.ends_with('/') {
+            url.push('/');
+        }
+    }
+    let _ = NETWORK.set(config);
+}
+
+fn network() -> &'static NetworkConfig {
+    NETWORK.get_or_init(NetworkConfig::default)
+}
+
+/// Errors of the requests that failed since the last call, oldest first
+pub fn take_failures() -> Vec<String> {
+    FAILURES.lock().map(|mut f| std::mem::take(&mut *f)).unwrap_or_default()
+}
+
+fn client() -> Client {
+    Client::builder()
+        .timeout(Duration::from_secs(network().timeout_secs))
+        .build()
+        .unwrap_or_default()
+}
+
+// Waits for a free slot under the requests per second limit
+async fn throttle() {
+    let rate = network().requests_per_second;
+    if rate == 0 {
+        return;
+    }
+    let wait = match NEXT_REQUEST.lock() {
+        Ok(mut next) => {
+            let now = Instant::now();
+            let slot = next.map_or(now, |n| n.max(now));
+            *next = Some(slot + Duration::from_secs(1) / rate);
+            slot - now
+        }
+        Err(_) => return,
+    };
+    if wait > Duration::from_millis(0) {
+        delay_for(wait).await;
+    }
+}
+
+// One attempt at fetching `path` below `base`. The error says whether trying again could help
+async fn attempt(client: &Client, base: &str, path: &str) -> Result<Value, (Error, bool)> {
+    throttle().await;
+    let resp = match client.get(format!("{}{}", base, path).as_str()).send().await {
+        Ok(resp) => resp,
+        Err(e) if e.is_timeout() => return Err((Error::new(ErrorKind::TimedOut, format!("{}: timed out", path)), true)),
+        Err(_) => return Err((Error::new(ErrorKind::NotConnected, format!("{}: could not access HackerNews", path)), true)),
+    };
+    let status = resp.status();
+    if !status.is_success() {
+        let transient = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+        return Err((Error::other(format!("{}: {}", path, status)), transient));
+    }
+    resp.json().await.map_err(|e| {
+        if e.is_timeout() {
+            (Error::new(ErrorKind::TimedOut, format!("{}: timed out", path)), true)
+        } else {
+            (Error::new(ErrorKind::InvalidData, format!("{}: not valid JSON", path)), false)
+        }
+    })
+}
+
+/// Fetches `path` below the API url, retrying transient failures with exponential backoff
+pub async fn fetch(client: &Client, path: &str) -> Result<Value, Error> {
+    fetch_from(client, network().api_url.as_str(), path).await
+}
+
+async fn fetch_from(client: &Client, base: &str, path: &str) -> Result<Value, Error> {
+    let config = network();
+    let mut backoff = Duration::from_millis(config.backoff_ms);
+    let mut retries = config.retries;
+    loop {
+        match attempt(client, base, path).await {
+            Ok(json) => return Ok(json),
+            Err((_, true)) if retries > 0 => {
+                retries -= 1;
+                delay_for(backoff).await;
+                backoff *= 2;
+            }
+            Err((e, _)) => {
+                if let Ok(mut failures) = FAILURES.lock() {
+                    if failures.len() < MAX_FAILURES {
+                        failures.push(e.to_string());
+                    }
+                }
+                return Err(e);
+            }
+        }
+    }
+}
+
+// `fetch` for code that isn't async
+fn fetch_blocking(path: &str) -> Result<Value, Error> {
+    let mut rt = Runtime::new()?;
+    rt.block_on(fetch(&client(), path))
+}
+
+// Ids of the stories an Algolia `endpoint` finds with the query string `params`
+fn search_ids(endpoint: &str, params: &[(&str, String)]) -> Result<Vec<String>, Error> {
+    let base = network().search_url.as_str();
+    let mut url = Url::parse(format!("{}{}", base, endpoint).as_str())
+        .map_err(|e| Error::new(ErrorKind::InvalidInput, format!("{}: {}", base, e)))?;
+    url.query_pairs_mut()
+        .extend_pairs(params.iter().filter(|(_, value)| !value.is_empty()))
+        .append_pair("hitsPerPage", SEARCH_HITS.to_string().as_str());
+    // Encoded by `Url`, so it can go after the base as it is
+    let path = format!("{}?{}", endpoint, url.query().unwrap_or_default());
+    let mut rt = Runtime::new()?;
+    let content = rt.block_on(fetch_from(&client(), base, path.as_str()))?;
+    match content.get("hits").and_then(|hits| hits.as_array()) {
+        Some(hits) => Ok(hits.iter()
+            .filter_map(|hit| hit.get("objectID").and_then(|id| id.as_str()))
+            .map(|id| id.to_string())
+            .collect()),
+        None => Err(Error::new(ErrorKind::InvalidData, format!("{}: not a list of search results", endpoint))),
+    }
+}
+
+/// Reads a front page day written as `2026-10-01`
+pub fn parse_day(date: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(date.trim(), DAY_FORMAT).map_err(|_| format!("{} is not a date like 2026-10-01", date.trim()))
+}
+
+pub fn format_day(day: NaiveDate) -> String {
+    day.format(DAY_FORMAT).to_string()
+}
+
+/// The feed of the stories on the front page of `day`
+pub fn front_page(day: NaiveDate) -> ListType {
+    ListType::FrontPage { date: format_day(day) }
+}
+
+// The unix times `date` starts and ends at in UTC
+fn day_range(date: &str) -> Result<(i64, i64), Error> {
+    let day = parse_day(date).map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+    let start = day.and_hms_opt(0, 0, 0).map(|t| t.and_utc().timestamp()).unwrap_or(0);
+    Ok((start, start + 60 * 60 * 24))
+}
+
+pub fn item_path(id: i64) -> String {
+    format!("{}{}.json", URI_ITEM, id)
+}
+
+/// Formats a unix timestamp from the API the way HN does, e.g. `3 hours ago`
+pub fn time_ago(time: i64) -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(time);
+    let seconds = (now - time).max(0);
+    let (n, unit) = match seconds {
+        s if s < 60 * 60 => (s / 60, "minute"),
+        s if s < 60 * 60 * 24 => (s / (60 * 60), "hour"),
+        s if s < 60 * 60 * 24 * 365 => (s / (60 * 60 * 24), "day"),
+        s => (s / (60 * 60 * 24 * 365), "year"),
+    };
+    if n == 1 {
+        format!("{} {} ago", n, unit)
+    } else {
+        format!("{} {}s ago", n, unit)
+    }
 }
 
 pub fn get_stories(story_type: &ListType) -> Result<Vec<String>, Error> {
@@ -34,88 +296,224 @@ pub fn get_stories(story_type: &ListType) -> Result<Vec<String>, Error> {
         ListType::BestStories => URI_BEST_STORIES,
         ListType::AskStories => URI_ASK_STORIES,
         ListType::ShowStories => URI_SHOW_STORIES,
-        ListType::JobStories => URI_JOB_STORIES
+        ListType::JobStories => URI_JOB_STORIES,
+        ListType::Search { query, numeric_filters } => return search_ids(URI_SEARCH, &[
+            ("query", query.clone()),
+            ("tags", "story".to_string()),
+            ("numericFilters", numeric_filters.clone()),
+        ]),
+        ListType::User { name } => return search_ids(URI_SEARCH_BY_DATE, &[
+            ("tags", format!("story,author_{}", name)),
+        ]),
+        ListType::FrontPage { date } => {
+            let (start, end) = day_range(date)?;
+            return search_ids(URI_SEARCH, &[
+                ("tags", "front_page".to_string()),
+                ("numericFilters", format!("created_at_i>={},created_at_i<{}", start, end)),
+            ]);
+        }
     };
-    let resp = match blocking::get(format!("{}{}.json", URI_PREFIX, endpoint).as_str()) {
-        Ok(resp) => resp,
-        Err(_) => return Err(Error::new(ErrorKind::NotConnected, "Could not access HackerNews"))
+    let content = fetch_blocking(format!("{}.json", endpoint).as_str())?;
+    let ids = match content.as_array() {
+        Some(ids) => ids.iter().map(|id| id.to_string()).collect(),
+        None => return Err(Error::new(ErrorKind::InvalidData, format!("{}.json is not a list of ids", endpoint))),
     };
-    let content: Vec<Number> = resp.json().expect("Could not get response text.");
-    let items = content.into_iter().map(|val: Number| val.to_string()).collect();
-    return Ok(items);
+    Ok(ids)
 }
 
-pub fn get_items(ids: &[String]) -> Result<Vec<Map<String, Value>>, Error> {
+// Ids that aren't numbers come back as empty items, like ones that don't exist
+fn load_items(ids: &[String], refresh: bool) -> Result<Vec<Item>, Error> {
+    let ids: Vec<i64> = ids.iter().map(|id| id.parse().unwrap_or(0)).collect();
     let mut rt = Runtime::new()?;
-    let client = Client::new();
-    let results = rt.block_on(
-        join_all(
-            ids
-                .iter()
-                .map(|id| {
-                    client.get(format!("{}{}{}.json", URI_PREFIX, URI_ITEM, id.as_str()).as_str()).send()
-                })
-        )
-    );
-
-    let items: Vec<Map<String, Value>> = results
-        .into_iter()
-        .map(|result| {
-            let resp = result.expect("Could not get response");
-            match rt.block_on(resp.json()) {
-                Ok(json) => json,
-                Err(_) => Map::new()
-            }
-        }).collect();
-    Ok(items)
+    let client = client();
+    Ok(rt.block_on(store::load(&client, ids.as_slice(), refresh)))
+}
+
+/// The items `ids`, in the same order, from the item store when it has recent copies. Items
+/// that fail to load come back empty and are reported by `take_failures`
+pub fn get_items(ids: &[String]) -> Result<Vec<Item>, Error> {
+    load_items(ids, false)
+}
+
+/// Like `get_items`, but always fetches the items again, for checking them for changes
+pub fn refresh_items(ids: &[String]) -> Result<Vec<Item>, Error> {
+    load_items(ids, true)
 }
 
+/// `get_items` by id, for handing over from a worker thread
+pub fn get_item_map(ids: Vec<i64>) -> HashMap<i64, Item> {
+    let strings: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    let mut items = get_items(strings.as_slice()).unwrap_or_default().into_iter();
+    ids.into_iter().map(|id| (id, items.next().unwrap_or_default())).collect()
+}
+
+pub fn get_item(id: &str) -> Result<Map<String, Value>, Error> {
+    let item = get_items(&[id.to_string()])?.pop().unwrap_or_default();
+    if item.is_empty() {
+        return Err(Error::new(ErrorKind::NotFound, format!("Item {} does not exist", id)));
+    }
+    Ok(Map::clone(&item))
+}
+
+/// Extracts an item id from either a bare id or an HN link such as
+/// `news.ycombinator.com/item?id=8863`
+pub fn parse_item_id(input: &str) -> Option<String> {
+    let input = input.trim();
+    let id = match input.find("id=") {
+        Some(i) => input[i + 3..].split('&').next().unwrap_or(""),
+        None => input,
+    };
+    if !id.is_empty() && id.chars().all(|c| c.is_ascii_digit()) {
+        Some(id.to_string())
+    } else {
+        None
+    }
+}
+
+/// Follows the `parent` links of item `id` up to the story it belongs to. Returns the
+/// story and, when `id` was a comment, the id of that comment
+pub fn get_root(id: &str) -> Result<(Map<String, Value>, Option<i64>), Error> {
+    let mut item = get_item(id)?;
+    let mut comment = None;
+    let mut first = true;
+    while let Some(item_type @ "comment") | Some(item_type @ "pollopt") = item.get("type").and_then(|t| t.as_str()) {
+        if first && item_type == "comment" {
+            comment = item.get("id").and_then(|id| id.as_i64());
+        }
+        first = false;
+        let parent = match item.get("parent").and_then(|p| p.as_i64()) {
+            Some(p) => p,
+            None => break,
+        };
+        item = get_item(parent.to_string().as_str())?;
+    }
+    Ok((item, comment))
+}
+
+pub fn get_user(name: &str) -> Result<Map<String, Value>, Error> {
+    // Unknown users come back as `null`
+    match fetch_blocking(format!("{}{}.json", URI_USER, name).as_str())? {
+        Value::Object(user) => Ok(user),
+        _ => Err(Error::new(ErrorKind::NotFound, format!("User {} does not exist", name)))
+    }
+}
+
+/// A comment without its replies, `kids` holds their ids
+pub fn comment_from_json(json: &Item) -> Comment {
+    let comment = str_field(json, "text").to_string();
+    let author = match str_field(json, "by") {
+        "" => "[deleted]".to_string(),
+        by => by.to_string(),
+    };
+    let id = json.get("id").and_then(|id| id.as_i64()).unwrap_or(0);
+    let time = json.get("time").and_then(|t| t.as_i64()).unwrap_or(0);
+    let kids = json.get("kids")
+        .and_then(|kids| kids.as_array())
+        .map(|kids| kids.iter().filter_map(|kid| kid.as_i64()).collect())
+        .unwrap_or_default();
+    Comment{
+        id,
+        author,
+        time,
+        text: comment,
+        replies: None,
+        kids,
+        collapsed: false,
+        pending: false,
+        item: json.clone(),
+    }
+}
 
 #[async_recursion]
 async fn comment_helper(ids: &[i64], client: &Client) -> Vec<Comment> {
-    let results = join_all(
-        ids
-            .into_iter()
-            .map(|id| {
-                client.get(format!("{}{}{}.json", URI_PREFIX, URI_ITEM, id).as_str()).send()
-            })
-    ).await;
-    let comments = join_all(results.into_iter()
-        .map(|result| async {
-            let resp = result.expect("Could not get response for comment");
-            let json: Map<String, Value> = match resp.json().await {
-                Ok(json) => json,
-                Err(_) => Map::new()
-            };
-            let comment = match json.get("text") {
-                Some(j) => j.as_str().unwrap().to_string(),
-                None => "".to_string(),
-            };
-            let kids = match json.get("kids") {
-                Some(kids) => {
-                    let kids: Vec<i64> = kids.as_array()
-                        .unwrap()
-                        .into_iter()
-                        .map(|kid|{
-                            kid.as_i64().unwrap()
-                         }).collect();
-                    Some(comment_helper(kids.as_slice(), client).await)
-                },
-                None => None
-            };
-            Comment{
-                text: comment,
-                replies: kids
+    let items = store::load(client, ids, false).await;
+    join_all(items.iter()
+        .map(|json| async move {
+            let mut comment = comment_from_json(json);
+            if !comment.kids.is_empty() {
+                comment.replies = Some(comment_helper(comment.kids.as_slice(), client).await);
             }
-        })).await;
-
-    comments
-
+            comment
+        })).await
 }
 
+/// Fetches the whole comment tree below `ids`
 pub fn get_comments(ids: &[i64]) -> Result<Vec<Comment>, Error> {
     let mut rt = Runtime::new()?;
-    let client = Client::new();
+    let client = client();
     Ok(rt.block_on(comment_helper(ids, &client)))
 }
 
+/// Fetches only the comments `ids`, in the same order, leaving their replies to be loaded later.
+/// Comments that fail to load come back empty, like deleted ones
+pub fn get_comment_level(ids: &[i64]) -> Result<Vec<Comment>, Error> {
+    let items = get_items(&ids.iter().map(|id| id.to_string()).collect::<Vec<String>>())?;
+    Ok(items.iter().map(comment_from_json).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_server::{init_network, Response, TestServer};
+
+    fn fetch_test(server: &TestServer, path: &str) -> Result<Value, Error> {
+        init_network();
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(fetch_from(&client(), server.url.as_str(), path))
+    }
+
+    #[test]
+    fn retries_after_a_timeout() {
+        let server = TestServer::start(|_, n| match n {
+            0 => Response::new(200, "1").delay(Duration::from_millis(1500)),
+            _ => Response::new(200, "2"),
+        });
+        assert_eq!(fetch_test(&server, "slow.json").unwrap(), Value::from(2));
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        assert!(requests.iter().all(|r| r.method == "GET" && r.path == "/slow.json"));
+    }
+
+    #[test]
+    fn backs_off_after_server_errors() {
+        let server = TestServer::start(|_, n| match n {
+            0 => Response::new(503, ""),
+            1 => Response::new(429, ""),
+            _ => Response::new(200, "[1,2]"),
+        });
+        assert_eq!(fetch_test(&server, "busy.json").unwrap(), serde_json::json!([1, 2]));
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        assert!(requests[1].time - requests[0].time >= Duration::from_millis(50));
+        assert!(requests[2].time - requests[1].time >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn reports_requests_that_run_out_of_retries() {
+        let server = TestServer::start(|_, _| Response::new(500, ""));
+        assert!(fetch_test(&server, "broken.json").is_err());
+        assert_eq!(server.requests().len(), 3);
+        assert!(take_failures().iter().any(|f| f.starts_with("broken.json: 500")));
+    }
+
+    #[test]
+    fn does_not_retry_client_errors() {
+        let server = TestServer::start(|_, _| Response::new(404, ""));
+        assert!(fetch_test(&server, "missing.json").is_err());
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn limits_requests_per_second() {
+        init_network();
+        let server = TestServer::start(|_, _| Response::new(200, "null"));
+        let client = client();
+        let mut rt = Runtime::new().unwrap();
+        let fetches = (0..10).map(|i| fetch_from(&client, server.url.as_str(), if i % 2 == 0 { "a" } else { "b" }));
+        rt.block_on(join_all(fetches));
+        let requests = server.requests();
+        assert_eq!(requests.len(), 10);
+        // Nine gaps of at least 50ms at 20 requests a second
+        assert!(requests[9].time - requests[0].time >= Duration::from_millis(400));
+    }
+}
@@ -0,0 +1,197 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::hn_api::{get_items, get_user, refresh_items};
+use crate::output::int_field;
+use crate::store::Item;
+
+const INBOX_DIR: &str = "hn_terminal";
+const INBOX_FILE: &str = "inbox.json";
+// Replies kept in the inbox once read
+const MAX_REPLIES: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    // User whose submissions are watched for replies, the inbox is off when not set
+    pub username: Option<String>,
+    pub interval_secs: u64,
+    // Ring the terminal bell when new replies arrive
+    pub bell: bool,
+    // How many of the user's most recent submissions are checked
+    pub submissions: usize,
+}
+
+impl Default for NotificationConfig {
+    fn default() -> NotificationConfig {
+        NotificationConfig {
+            username: None,
+            interval_secs: 300,
+            bell: false,
+            submissions: 30,
+        }
+    }
+}
+
+// What was seen on the last poll, kept between runs
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct Snapshot {
+    username: String,
+    // Replies of each submission at the last poll
+    kids: HashMap<i64, Vec<i64>>,
+    // Newest first
+    replies: Vec<i64>,
+    unread: Vec<i64>,
+}
+
+/// What a poll fetched, handed from the worker thread to `Inbox::finish_poll`
+pub struct InboxPoll {
+    // Replies of each submission now
+    kids: HashMap<i64, Vec<i64>>,
+    // Replies that weren't there on the last poll, newest first
+    new_replies: Vec<i64>,
+    // The replies the inbox lists after this poll, newest first
+    items: Vec<Item>,
+}
+
+/// Replies to a user's recent submissions, found by diffing their `kids` between polls
+pub struct Inbox {
+    config: NotificationConfig,
+    username: String,
+    snapshot: Snapshot,
+    last_poll: Option<Instant>,
+    // Whether a poll is running on a worker thread
+    polling: bool,
+}
+
+impl Inbox {
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(INBOX_DIR).join(INBOX_FILE))
+    }
+
+    /// Loads the stored snapshot, or returns `None` when no username is configured
+    pub fn new(config: &NotificationConfig) -> Option<Inbox> {
+        let username = config.username.clone()?;
+        let snapshot = Inbox::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str::<Snapshot>(contents.as_str()).ok())
+            .filter(|s| s.username == username)
+            .unwrap_or_default();
+        Some(Inbox {
+            config: config.clone(),
+            username,
+            snapshot,
+            last_poll: None,
+            polling: false,
+        })
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let path = match Inbox::path() {
+            Some(p) => p,
+            None => return Err(Error::new(ErrorKind::NotFound, "Could not find data directory")),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string(&self.snapshot)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        fs::write(path, contents)
+    }
+
+    pub fn due(&self) -> bool {
+        match self.last_poll {
+            _ if self.polling => false,
+            Some(t) => t.elapsed() >= Duration::from_secs(self.config.interval_secs),
+            None => true,
+        }
+    }
+
+    pub fn bell(&self) -> bool {
+        self.config.bell
+    }
+
+    /// Starts a poll, returning the fetching to run on a worker thread. It checks the user's
+    /// recent submissions for replies that weren't there on the last poll
+    pub fn start_poll(&mut self) -> impl FnOnce() -> Result<InboxPoll, Error> + Send + 'static {
+        self.last_poll = Some(Instant::now());
+        self.polling = true;
+        let username = self.username.clone();
+        let submissions = self.config.submissions;
+        let snapshot = self.snapshot.clone();
+        move || {
+            let user = get_user(username.as_str())?;
+            let submitted: Vec<String> = user.get("submitted")
+                .and_then(|s| s.as_array())
+                .map(|s| s.iter().take(submissions).map(|id| id.to_string()).collect())
+                .unwrap_or_default();
+            let items = refresh_items(submitted.as_slice())?;
+
+            // The first poll only records what is there
+            let first_poll = snapshot.username.is_empty();
+            let mut new_replies: Vec<i64> = Vec::new();
+            let mut kids = HashMap::new();
+            for item in &items {
+                let id = int_field(item, "id");
+                let item_kids: Vec<i64> = item.get("kids")
+                    .and_then(|k| k.as_array())
+                    .map(|k| k.iter().filter_map(|kid| kid.as_i64()).collect())
+                    .unwrap_or_default();
+                if !first_poll {
+                    let seen: HashSet<&i64> = snapshot.kids.get(&id).map(|k| k.iter().collect()).unwrap_or_default();
+                    new_replies.extend(item_kids.iter().filter(|kid| !seen.contains(kid)));
+                }
+                kids.insert(id, item_kids);
+            }
+            // Higher ids are newer
+            new_replies.sort_unstable_by(|a, b| b.cmp(a));
+            let ids: Vec<String> = new_replies.iter()
+                .chain(snapshot.replies.iter())
+                .take(MAX_REPLIES)
+                .map(|id| id.to_string())
+                .collect();
+            let items = get_items(ids.as_slice())?;
+            Ok(InboxPoll { kids, new_replies, items })
+        }
+    }
+
+    /// Takes in a poll started by `start_poll`, returning how many new replies it found and
+    /// the replies to list
+    pub fn finish_poll(&mut self, poll: Result<InboxPoll, Error>) -> Result<(usize, Vec<Item>), Error> {
+        self.polling = false;
+        let poll = poll?;
+        self.snapshot.username = self.username.clone();
+        self.snapshot.kids = poll.kids;
+        for reply in poll.new_replies.iter().rev() {
+            self.snapshot.replies.insert(0, *reply);
+            self.snapshot.unread.push(*reply);
+        }
+        self.snapshot.replies.truncate(MAX_REPLIES);
+        let replies = &self.snapshot.replies;
+        self.snapshot.unread.retain(|id| replies.contains(id));
+        self.save()?;
+        Ok((poll.new_replies.len(), poll.items))
+    }
+
+    pub fn mark_read(&mut self, id: i64) {
+        if self.snapshot.unread.contains(&id) {
+            self.snapshot.unread.retain(|r| *r != id);
+            // Losing the read state only shows the reply as unread again
+            let _ = self.save();
+        }
+    }
+
+    /// Tab title with the number of unread replies
+    pub fn title(&self) -> String {
+        match self.snapshot.unread.len() {
+            0 => "Inbox".to_string(),
+            n => format!("Inbox ({})", n),
+        }
+    }
+}
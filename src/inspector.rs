@@ -0,0 +1,174 @@
+use std::io::{self, Error, Write};
+use std::process::{Command, Stdio};
+
+use serde_json::Value;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::Rect;
+use tui::widgets::{Block, Borders, BorderType, Paragraph, Text};
+use unicode_width::UnicodeWidthChar;
+
+use crate::colors::{get_style, HNStyles};
+use crate::output::{int_field, str_field};
+use crate::store::Item;
+
+// Kids listed by id in the summary, the rest are only counted
+const LISTED_KIDS: usize = 10;
+// Commands that put what they read on the clipboard, tried in order
+const CLIPBOARD_COMMANDS: [&[&str]; 4] = [
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["pbcopy"],
+];
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Puts `text` on the clipboard with the first clipboard tool that works, or else asks the
+/// terminal to with an OSC 52 sequence
+pub fn copy(text: &str) -> Result<(), Error> {
+    for command in CLIPBOARD_COMMANDS.iter() {
+        let mut child = match Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn() {
+            Ok(child) => child,
+            Err(_) => continue,
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            return Ok(());
+        }
+    }
+    print!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    io::stdout().flush()
+}
+
+// `line` cut into rows of `width` columns. Unlike word wrapping this keeps the indentation
+fn hard_wrap(line: &str, width: usize, rows: &mut Vec<String>) {
+    let mut row = String::new();
+    let mut used = 0;
+    for c in line.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w > width && !row.is_empty() {
+            rows.push(std::mem::take(&mut row));
+            used = 0;
+        }
+        row.push(c);
+        used += w;
+    }
+    rows.push(row);
+}
+
+/// A story or comment as the API returned it, drawn over the current tab
+pub struct Inspector {
+    item: Item,
+    lines: Vec<String>,
+    // `lines` wrapped to the width of the pane
+    rows: Vec<String>,
+    width: u16,
+    scroll: u16,
+    // Rows of the pane when it was last drawn
+    height: u16,
+}
+
+impl Inspector {
+    pub fn new(item: Item) -> Inspector {
+        let mut lines = Vec::new();
+        if item.is_empty() {
+            lines.push("Nothing from the API yet, replies posted from here show up once HN has them".to_string());
+        } else {
+            let kids: Vec<i64> = item.get("kids")
+                .and_then(|k| k.as_array())
+                .map(|k| k.iter().filter_map(Value::as_i64).collect())
+                .unwrap_or_default();
+            let mut listed: Vec<String> = kids.iter().take(LISTED_KIDS).map(|k| k.to_string()).collect();
+            if kids.len() > LISTED_KIDS {
+                listed.push(format!("and {} more", kids.len() - LISTED_KIDS));
+            }
+            let parent = match int_field(&item, "parent") {
+                0 => "-".to_string(),
+                parent => parent.to_string(),
+            };
+            lines.push(format!("id      {}", int_field(&item, "id")));
+            lines.push(format!("type    {}", str_field(&item, "type")));
+            lines.push(format!("parent  {}", parent));
+            lines.push(match kids.len() {
+                0 => "kids    0".to_string(),
+                n => format!("kids    {}: {}", n, listed.join(", ")),
+            });
+            lines.push(String::new());
+            lines.extend(serde_json::to_string_pretty(&*item).unwrap_or_default().lines().map(|l| l.to_string()));
+        }
+        Inspector { item, lines, rows: Vec::new(), width: 0, scroll: 0, height: 0 }
+    }
+
+    pub fn id(&self) -> i64 {
+        int_field(&self.item, "id")
+    }
+
+    pub fn json(&self) -> String {
+        serde_json::to_string_pretty(&*self.item).unwrap_or_default()
+    }
+
+    pub fn down(&mut self) {
+        self.scroll = (self.scroll + 1).min(self.rows.len().saturating_sub(1) as u16);
+    }
+
+    pub fn up(&mut self) {
+        self.scroll = self.scroll.saturating_sub(1);
+    }
+
+    pub fn page(&mut self, down: bool) {
+        for _ in 0..self.height.max(1) {
+            if down { self.down() } else { self.up() }
+        }
+    }
+
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, area: Rect) {
+        let title = format!(" Item {} - y copies the id, Y the JSON ", self.id());
+        let block = Block::default()
+            .title(title.as_str())
+            .title_style(get_style(HNStyles::WhiteTitle))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Double)
+            .border_style(get_style(HNStyles::OrangeBorder));
+        let inner = block.inner(area);
+        self.height = inner.height;
+        if inner.width != self.width {
+            self.width = inner.width;
+            self.rows.clear();
+            for line in self.lines.iter() {
+                hard_wrap(line, inner.width.max(1) as usize, &mut self.rows);
+            }
+            self.scroll = self.scroll.min(self.rows.len().saturating_sub(1) as u16);
+        }
+        let text: Vec<Text> = self.rows.iter()
+            .skip(self.scroll as usize)
+            .take(self.height as usize)
+            .map(|l| Text::raw(format!("{}\n", l)))
+            .collect();
+        let paragraph = Paragraph::new(text.iter())
+            .block(block)
+            .style(get_style(HNStyles::WhiteBlock));
+        f.render_widget(paragraph, area);
+    }
+}
@@ -1,16 +1,21 @@
 use std::error::Error;
-use std::io;
+use std::io::{self, Stdout, Write};
+use std::process;
+use std::time::{Duration, Instant};
 
-use termion::event::Key;
-use termion::raw::IntoRawMode;
+use chrono::{NaiveDate, Utc};
+use clap::Parser;
+use termion::event::{Key, MouseButton, MouseEvent};
+use termion::input::MouseTerminal;
+use termion::raw::{IntoRawMode, RawTerminal};
 use termion::screen::AlternateScreen;
 use tui::{
     backend::TermionBackend,
     Frame,
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Style, Modifier},
     Terminal,
-    widgets::{Block, Borders, Tabs},
+    widgets::{Block, Borders, Paragraph, Tabs, Text},
 };
 use tui::backend::Backend;
 
@@ -24,34 +29,270 @@ mod tabs;
 mod story_block;
 mod comment_block;
 mod colors;
+mod config;
+mod cli;
+mod output;
+mod prompt;
+mod export;
+mod session;
+mod editor;
+mod submit_form;
+mod inbox;
+mod watch;
+mod hiring;
+mod search;
+mod history;
+mod reads;
+mod state;
+mod store;
+mod filter;
+mod inspector;
+#[cfg(test)]
+mod test_server;
 
-use crate::event::{Event, Events};
-use crate::hn_api::ListType;
+use crate::cli::Cli;
+use crate::event::{contains, Event, Events};
+use crate::story_list::StoryList;
 use crate::story_screen::StoryScreen;
-use crate::tabs::TabsState;
+use crate::tabs::{FeedTab, TabsState};
 use crate::colors::{HNStyles, get_style, HN_ORANGE, HN_BACKGROUND};
+use crate::config::{Config, LayoutMode};
+use crate::export::ExportFormat;
+use crate::filter::{ListFilter, SortMode};
+use crate::hiring::HiringView;
+use crate::history::HistoryView;
+use crate::hn_api::{format_day, front_page, get_root, parse_day, parse_item_id, ListType};
+use crate::inbox::{Inbox, InboxPoll};
+use crate::inspector::{copy, Inspector};
+use crate::prompt::{Prompt, PromptResult};
+use crate::reads::ReadingLog;
+use crate::state::{tab_name, UiState};
+use crate::store::Item;
+use crate::session::{Action, Session};
+use crate::submit_form::SubmitForm;
+use crate::watch::WatchList;
+use crate::output::str_field;
 
 struct App {
     events: Events,
     screens: Vec<StoryScreen>,
     tabs: TabsState,
+    config: Config,
+    // Layout to go back to when leaving zoom
+    unzoomed: LayoutMode,
+    tabs_area: Rect,
+    // Time and cell of the previous left click, to detect double clicks
+    last_click: Option<(Instant, u16, u16)>,
+    // Text input currently taking the keyboard
+    prompt: Option<(PromptKind, Prompt)>,
+    // Message shown at the bottom of the screen until the next key press
+    status: Option<String>,
+    // Logged in on the first action that needs it
+    session: Option<Session>,
+    // Reply waiting for the main loop to hand the terminal to the editor, as the
+    // parent's id and the text to pre-fill
+    editing: Option<(i64, String)>,
+    submit_form: Option<SubmitForm>,
+    // Job table of a hiring thread, drawn over the current tab
+    hiring: Option<HiringView>,
+    // Search over everything read so far, drawn over the current tab
+    history: Option<HistoryView>,
+    // Raw JSON of the selected story or comment, drawn over the current tab
+    inspector: Option<Inspector>,
+    // Listed in the tab after the feeds
+    watching: WatchList,
+    // Stories opened so far, listed in the History tab after Watching
+    reads: ReadingLog,
+    // Replies to the configured user, shown in the last tab
+    inbox: Option<Inbox>,
 }
 
+// What the text typed into the prompt is used for
+enum PromptKind {
+    GoToItem,
+    Export,
+    HiringLocation,
+    HiringKeyword,
+    Search,
+    HistoryQuery,
+    ClearReads,
+    GoToRank,
+    AddTab,
+    CloseTab,
+    FrontPage,
+    // Holds the filter to go back to when the filter bar is cancelled
+    Filter(ListFilter),
+}
+
+const DOUBLE_CLICK_INTERVAL: Duration = Duration::from_millis(400);
+const FILTER_LABEL: &str = "Filter (points:N comments:N age:6h domain:x -domain:x type:ask)";
+const FRONT_PAGE_LABEL: &str = "Front page of (YYYY-MM-DD)";
+const ADD_TAB_LABEL: &str = "New tab (top, new, best, ask, show, job, user NAME, front YYYY-MM-DD or a search)";
+
 impl App {
-    fn new() -> App {
-        App {
+    fn new(config: Config) -> App {
+        let mut app = App {
             events: Events::new(),
-            screens: vec![
-                StoryScreen::new(ListType::TopStories),
-                StoryScreen::new(ListType::NewStories),
-                StoryScreen::new(ListType::BestStories),
-                StoryScreen::new(ListType::AskStories),
-                StoryScreen::new(ListType::ShowStories),
-                StoryScreen::new(ListType::JobStories),
-            ],
-            tabs: TabsState::new(),
+            screens: Vec::new(),
+            tabs: TabsState::new(Vec::new()),
+            unzoomed: LayoutMode::Vertical,
+            tabs_area: Rect::default(),
+            last_click: None,
+            prompt: None,
+            status: None,
+            session: None,
+            editing: None,
+            submit_form: None,
+            hiring: None,
+            history: None,
+            inspector: None,
+            watching: WatchList::load(&config.watching),
+            reads: ReadingLog::load(&config.reading),
+            inbox: Inbox::new(&config.notifications),
+            config,
+        };
+        for tab in app.config.tabs.feeds.clone() {
+            let screen = app.feed_screen(&tab);
+            app.screens.push(screen);
+            app.tabs.titles.push(tab.title());
+        }
+        app.screens.push(StoryScreen::from_list(StoryList::from_items(Vec::new())));
+        app.tabs.titles.push("Watching".to_string());
+        app.screens.push(StoryScreen::from_list(StoryList::from_items(Vec::new())));
+        app.tabs.titles.push("History".to_string());
+        app.refresh_reads();
+        // Filled in by the first poll
+        if let Some(inbox) = app.inbox.as_ref() {
+            app.screens.push(StoryScreen::from_list(StoryList::from_items(Vec::new())));
+            app.tabs.titles.push(inbox.title());
+        }
+        app
+    }
+
+    // A screen listing the stories of `tab`, with its filter applied
+    fn feed_screen(&mut self, tab: &FeedTab) -> StoryScreen {
+        let mut screen = StoryScreen::new(&tab.feed, &self.config.list);
+        match ListFilter::parse(tab.filter.as_str()) {
+            Ok(filter) => screen.story_list.set_view(SortMode::Rank, filter),
+            Err(e) => self.status = Some(format!("Invalid filter for {}: {}", tab.title(), e)),
+        }
+        screen
+    }
+
+    fn watching_tab(&self) -> usize {
+        self.config.tabs.feeds.len()
+    }
+
+    fn reads_tab(&self) -> usize {
+        self.config.tabs.feeds.len() + 1
+    }
+
+    /// Adds a tab for the feed typed into the new tab prompt
+    fn add_tab(&mut self, input: &str) {
+        match FeedTab::parse(input) {
+            Ok(tab) => self.insert_tab(tab),
+            Err(e) => self.status = Some(format!("Not a feed: {}", e)),
+        }
+    }
+
+    // Adds `tab` after the other feeds and switches to it
+    fn insert_tab(&mut self, tab: FeedTab) {
+        let index = self.config.tabs.feeds.len();
+        let screen = self.feed_screen(&tab);
+        self.screens.insert(index, screen);
+        self.tabs.titles.insert(index, tab.title());
+        self.config.tabs.feeds.push(tab);
+        self.save_config();
+        // The tabs after it moved along, keep the current one
+        if self.tabs.index >= index {
+            self.tabs.index += 1;
+        }
+        self.select_tab(index);
+    }
+
+    // Day shown by the current tab, when it is a front page
+    fn front_page_day(&self) -> Option<NaiveDate> {
+        match self.config.tabs.feeds.get(self.tabs.index).map(|tab| &tab.feed) {
+            Some(ListType::FrontPage { date }) => parse_day(date).ok(),
+            _ => None,
+        }
+    }
+
+    fn open_front_page_picker(&mut self) {
+        let day = self.front_page_day().unwrap_or_else(|| Utc::now().date_naive() - chrono::Duration::days(1));
+        self.open_prompt(PromptKind::FrontPage, FRONT_PAGE_LABEL);
+        if let Some((_, prompt)) = self.prompt.as_mut() {
+            prompt.input = format_day(day);
+        }
+    }
+
+    /// Shows the front page of `day` in the current tab when it is a front page, or in a new tab
+    fn show_front_page(&mut self, day: NaiveDate) {
+        if day > Utc::now().date_naive() {
+            self.status = Some("There is no front page after today yet".to_string());
+            return;
+        }
+        let index = self.tabs.index;
+        if self.front_page_day().is_none() {
+            self.insert_tab(FeedTab::new(front_page(day)));
+            return;
         }
+        self.reads.stop();
+        let mut tab = self.config.tabs.feeds[index].clone();
+        tab.feed = front_page(day);
+        self.screens[index] = self.feed_screen(&tab);
+        self.tabs.titles[index] = tab.title();
+        self.config.tabs.feeds[index] = tab;
+        self.save_config();
     }
+
+    // Moves the current front page tab `days` days back or forward
+    fn step_front_page(&mut self, days: i64) {
+        match self.front_page_day() {
+            Some(day) => self.show_front_page(day + chrono::Duration::days(days)),
+            None => self.status = Some("Press D to pick a front page day first".to_string()),
+        }
+    }
+
+    fn confirm_close_tab(&mut self) {
+        if self.tabs.index >= self.config.tabs.feeds.len() {
+            self.status = Some("Only feed tabs can be closed".to_string());
+            return;
+        }
+        let label = format!("Close the tab {}? (y/n)", self.tabs.titles[self.tabs.index]);
+        self.open_prompt(PromptKind::CloseTab, label.as_str());
+    }
+
+    fn close_tab(&mut self) {
+        let index = self.tabs.index;
+        self.reads.stop();
+        self.screens.remove(index);
+        self.tabs.titles.remove(index);
+        let tab = self.config.tabs.feeds.remove(index);
+        self.save_config();
+        self.status = Some(format!("Closed {}", tab.title()));
+    }
+
+    /// Swaps the current feed tab with its neighbour on the left or the right
+    fn move_tab(&mut self, left: bool) {
+        let index = self.tabs.index;
+        let feeds = self.config.tabs.feeds.len();
+        if index >= feeds {
+            self.status = Some("Only feed tabs can be moved".to_string());
+            return;
+        }
+        let other = match left {
+            true if index > 0 => index - 1,
+            false if index + 1 < feeds => index + 1,
+            _ => return,
+        };
+        self.screens.swap(index, other);
+        self.tabs.titles.swap(index, other);
+        self.config.tabs.feeds.swap(index, other);
+        self.tabs.index = other;
+        self.save_config();
+    }
+
     fn draw<B: Backend>(&mut self, f: &mut Frame<B>) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
@@ -67,7 +308,15 @@ impl App {
 
         let main_block = Block::default().style(Style::new().bg(HN_BACKGROUND));
         f.render_widget(main_block, f.size());
-        self.screens[self.tabs.index].draw(f, chunks[1]);
+        if let Some(view) = self.inspector.as_mut() {
+            view.draw(f, chunks[1]);
+        } else if let Some(view) = self.history.as_mut() {
+            view.draw(f, chunks[1]);
+        } else if let Some(view) = self.hiring.as_mut() {
+            view.draw(f, chunks[1]);
+        } else {
+            self.screens[self.tabs.index].draw(f, chunks[1], &self.config.layout);
+        }
         let tabs = Tabs::default()
             .block(Block::default().borders(Borders::ALL)
                 .title("Hacker News").title_style(get_style(HNStyles::OrangeTitle).modifier(Modifier::BOLD))
@@ -77,7 +326,620 @@ impl App {
             .select(self.tabs.index)
             .style(get_style(HNStyles::OrangeBlock))
             .highlight_style(Style::default().fg(Color::Black).bg(HN_ORANGE));
+        self.tabs_area = chunks[0];
         f.render_widget(tabs, chunks[0]);
+
+        // The prompt and status messages use the bottom margin row
+        let size = f.size();
+        let bottom = Rect::new(size.x, size.bottom().saturating_sub(1), size.width, 1.min(size.height));
+        if let Some(form) = self.submit_form.as_ref() {
+            form.draw(f, size);
+        }
+        if let Some((_, prompt)) = self.prompt.as_ref() {
+            prompt.draw(f, bottom);
+        } else if let Some(status) = self.status.as_ref() {
+            let text = [Text::raw(status.as_str())];
+            f.render_widget(Paragraph::new(text.iter()).style(get_style(HNStyles::OrangeBlock)), bottom);
+        }
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind, label: &str) {
+        // Let `q` be typed instead of stopping the input thread
+        self.events.disable_exit_key();
+        self.prompt = Some((kind, Prompt::new(label)));
+    }
+
+    fn prompt_key(&mut self, key: Key) {
+        let searching = matches!(self.prompt, Some((PromptKind::Search, _)));
+        if searching && (key == Key::Ctrl('r') || key == Key::Ctrl('t')) {
+            // Switch between plain text and regex, or case handling, while typing
+            if key == Key::Ctrl('r') {
+                self.config.search.regex = !self.config.search.regex;
+            } else {
+                self.config.search.ignore_case = !self.config.search.ignore_case;
+            }
+            self.save_config();
+            self.update_search(false);
+            return;
+        }
+        let result = match self.prompt.as_mut() {
+            Some((_, prompt)) => prompt.key(key),
+            None => return,
+        };
+        let input = match result {
+            PromptResult::Editing => {
+                if searching {
+                    self.update_search(true);
+                }
+                self.update_filter();
+                return;
+            }
+            PromptResult::Cancel => None,
+            PromptResult::Submit(input) => Some(input),
+        };
+        let (kind, _) = self.prompt.take().unwrap();
+        self.events.enable_exit_key();
+        if let (PromptKind::Search, None) = (&kind, &input) {
+            self.screens[self.tabs.index].search(None);
+        }
+        if let (PromptKind::Filter(previous), None) = (&kind, &input) {
+            let list = &mut self.screens[self.tabs.index].story_list;
+            list.set_view(list.sort, previous.clone());
+        }
+        if let Some(input) = input {
+            match kind {
+                PromptKind::GoToItem => self.open_item(input.as_str()),
+                PromptKind::GoToRank => match input.trim().parse::<usize>() {
+                    Ok(rank) if rank > 0 => self.screens[self.tabs.index].jump_to(rank - 1),
+                    _ => self.status = Some(format!("Not a rank: {}", input)),
+                },
+                PromptKind::Export => self.export(input.as_str()),
+                PromptKind::AddTab => self.add_tab(input.as_str()),
+                PromptKind::FrontPage => match parse_day(input.as_str()) {
+                    Ok(day) => self.show_front_page(day),
+                    Err(e) => self.status = Some(e),
+                },
+                PromptKind::CloseTab => {
+                    if input.trim().eq_ignore_ascii_case("y") {
+                        self.close_tab();
+                    }
+                }
+                PromptKind::ClearReads => {
+                    if input.trim().eq_ignore_ascii_case("y") {
+                        self.status = Some(match self.reads.clear() {
+                            Ok(()) => "Reading history cleared".to_string(),
+                            Err(e) => format!("Could not clear the reading history: {}", e),
+                        });
+                        self.refresh_reads();
+                    }
+                }
+                PromptKind::HistoryQuery => {
+                    if let Some(view) = self.history.as_mut() {
+                        view.query = input;
+                        view.refresh();
+                    }
+                }
+                PromptKind::Filter(_) => {
+                    if let Err(e) = ListFilter::parse(input.as_str()) {
+                        self.status = Some(format!("Invalid filter: {}", e));
+                    }
+                }
+                PromptKind::Search => {
+                    if !input.is_empty() && !self.screens[self.tabs.index].next_match(true, true) {
+                        self.status = Some(format!("Not found: {}", input));
+                    }
+                }
+                PromptKind::HiringLocation | PromptKind::HiringKeyword => {
+                    if let Some(view) = self.hiring.as_mut() {
+                        match kind {
+                            PromptKind::HiringLocation => view.filter.location = input,
+                            _ => view.filter.keyword = input,
+                        }
+                        view.apply();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Searches the current tab for what is typed into the search prompt, showing the number
+    /// of matches in its label. With `jump` the first match is selected
+    fn update_search(&mut self, jump: bool) {
+        let query = match self.prompt.as_ref() {
+            Some((PromptKind::Search, prompt)) => prompt.input.clone(),
+            _ => return,
+        };
+        let label = self.config.search.label();
+        let screen = &mut self.screens[self.tabs.index];
+        let label = match self.config.search.pattern(query.as_str()) {
+            Ok(Some(pattern)) => {
+                let matches = screen.search(Some(&pattern));
+                if jump {
+                    screen.next_match(true, true);
+                }
+                format!("{} [{} {}]", label, matches, if matches == 1 { "match" } else { "matches" })
+            }
+            Ok(None) => {
+                screen.search(None);
+                label
+            }
+            Err(_) => {
+                screen.search(None);
+                format!("{} [invalid pattern]", label)
+            }
+        };
+        if let Some((_, prompt)) = self.prompt.as_mut() {
+            prompt.label = label;
+        }
+    }
+
+    fn open_filter(&mut self) {
+        let filter = self.screens[self.tabs.index].story_list.filter.clone();
+        let input = filter.describe();
+        self.open_prompt(PromptKind::Filter(filter), FILTER_LABEL);
+        if let Some((_, prompt)) = self.prompt.as_mut() {
+            prompt.input = input;
+        }
+    }
+
+    /// Filters the current tab by what is typed into the filter bar, showing how many
+    /// stories pass in its label. Terms that don't parse yet leave the list as it was
+    fn update_filter(&mut self) {
+        let input = match self.prompt.as_ref() {
+            Some((PromptKind::Filter(_), prompt)) => prompt.input.clone(),
+            _ => return,
+        };
+        let list = &mut self.screens[self.tabs.index].story_list;
+        let label = match ListFilter::parse(input.as_str()) {
+            Ok(filter) => {
+                list.set_view(list.sort, filter);
+                format!("{} [{} shown]", FILTER_LABEL, list.shown())
+            }
+            Err(e) => format!("{} [{}]", FILTER_LABEL, e),
+        };
+        if let Some((_, prompt)) = self.prompt.as_mut() {
+            prompt.label = label;
+        }
+    }
+
+    fn cycle_sort(&mut self) {
+        let list = &mut self.screens[self.tabs.index].story_list;
+        list.set_view(list.sort.next(), list.filter.clone());
+    }
+
+    fn next_match(&mut self, forward: bool) {
+        if !self.screens[self.tabs.index].next_match(forward, false) {
+            self.status = Some("No matches, press / to search".to_string());
+        }
+    }
+
+    fn export(&mut self, format: &str) {
+        let format = match ExportFormat::parse(format) {
+            Some(f) => f,
+            None => {
+                self.status = Some(format!("Unknown export format: {}", format));
+                return;
+            }
+        };
+        self.status = Some(match self.screens[self.tabs.index].export(&self.config.export, format) {
+            Ok(path) => format!("Exported to {}", path.display()),
+            Err(e) => format!("Could not export: {}", e),
+        });
+    }
+
+    fn session(&mut self) -> Result<&mut Session, std::io::Error> {
+        if self.session.is_none() {
+            self.session = Some(Session::login(&self.config.auth)?);
+        }
+        Ok(self.session.as_mut().unwrap())
+    }
+
+    /// Applies `action` to the selected comment, or the open story
+    fn act(&mut self, action: Action) {
+        let id = match self.screens[self.tabs.index].target() {
+            Some(id) => id,
+            None => {
+                self.status = Some("Open a story first".to_string());
+                return;
+            }
+        };
+        let result = self.session().and_then(|s| s.act(action, id));
+        self.status = Some(match result {
+            Ok(()) => format!("{} item {}", action.name(), id),
+            Err(e) => e.to_string(),
+        });
+    }
+
+    fn start_reply(&mut self) {
+        let (parent, text) = match self.screens[self.tabs.index].reply_target() {
+            Some(t) => t,
+            None => {
+                self.status = Some("Open a story first".to_string());
+                return;
+            }
+        };
+        // Log in before anything gets written
+        if let Err(e) = self.session() {
+            self.status = Some(e.to_string());
+            return;
+        }
+        self.editing = Some((parent, editor::quote(text.as_str())));
+    }
+
+    fn finish_reply(&mut self, parent: i64, edited: Result<Option<String>, std::io::Error>) {
+        let text = match edited {
+            Ok(Some(text)) => text,
+            Ok(None) => {
+                self.status = Some("Reply aborted, nothing was written".to_string());
+                return;
+            }
+            Err(e) => {
+                self.status = Some(format!("Could not edit reply: {}", e));
+                return;
+            }
+        };
+        let result = self.session().and_then(|s| s.reply(parent, text.as_str()).map(|id| (id, s.username.clone())));
+        match result {
+            Ok((id, username)) => {
+                self.screens[self.tabs.index].add_reply(parent, id, username.as_str(), text.as_str());
+                self.status = Some("Reply posted".to_string());
+            }
+            Err(e) => self.status = Some(format!("Could not post reply: {}", e)),
+        }
+    }
+
+    fn open_submit_form(&mut self) {
+        self.events.disable_exit_key();
+        self.submit_form = Some(SubmitForm::new());
+    }
+
+    fn submit_form_key(&mut self, key: Key) {
+        let result = match self.submit_form.as_mut() {
+            Some(form) => form.key(key),
+            None => return,
+        };
+        match result {
+            PromptResult::Editing => {}
+            PromptResult::Cancel => {
+                self.submit_form = None;
+                self.events.enable_exit_key();
+            }
+            PromptResult::Submit(_) => {
+                let form = self.submit_form.take().unwrap();
+                match self.session().and_then(|s| s.submit(form.title(), form.url(), form.text())) {
+                    Ok(()) => {
+                        self.status = Some("Story submitted".to_string());
+                        self.events.enable_exit_key();
+                    }
+                    Err(e) => {
+                        // Keep what was typed so it can be fixed
+                        self.status = Some(format!("Could not submit: {}", e));
+                        self.submit_form = Some(form);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Shows the jobs of the open hiring thread as a table
+    fn open_hiring(&mut self) {
+        let screen = &self.screens[self.tabs.index];
+        let (item, comments) = match (screen.item.as_ref(), screen.comment_block.as_ref()) {
+            (Some(item), Some(c)) => (item, c),
+            _ => {
+                self.status = Some("Open a \"Who is hiring?\" thread first".to_string());
+                return;
+            }
+        };
+        if !HiringView::is_hiring_thread(str_field(item, "title")) {
+            self.status = Some("Not a \"Who is hiring?\" thread".to_string());
+            return;
+        }
+        self.hiring = Some(HiringView::new(comments.comments.as_slice()));
+    }
+
+    fn hiring_key(&mut self, key: Key) {
+        let view = match self.hiring.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
+        match key {
+            Key::Esc | Key::Char('J') => self.hiring = None,
+            Key::Down => view.next(),
+            Key::Up => view.previous(),
+            Key::Char('s') => view.cycle_sort(),
+            Key::Char('r') => {
+                view.filter.remote = !view.filter.remote;
+                view.apply();
+            }
+            Key::Char('o') => {
+                view.filter.onsite = !view.filter.onsite;
+                view.apply();
+            }
+            Key::Char('v') => {
+                view.filter.visa = !view.filter.visa;
+                view.apply();
+            }
+            Key::Char('c') => {
+                view.filter = Default::default();
+                view.apply();
+            }
+            Key::Char('L') => {
+                let location = view.filter.location.clone();
+                self.open_prompt(PromptKind::HiringLocation, "Location");
+                if let Some((_, prompt)) = self.prompt.as_mut() {
+                    prompt.input = location;
+                }
+            }
+            Key::Char('k') => {
+                let keyword = view.filter.keyword.clone();
+                self.open_prompt(PromptKind::HiringKeyword, "Keyword");
+                if let Some((_, prompt)) = self.prompt.as_mut() {
+                    prompt.input = keyword;
+                }
+            }
+            // Read the whole post in the thread
+            Key::Char('\n') => {
+                if let Some(id) = view.selected() {
+                    self.hiring = None;
+                    self.screens[self.tabs.index].show_comment(id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn history_key(&mut self, key: Key) {
+        let view = match self.history.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
+        match key {
+            Key::Esc | Key::Char('H') => self.history = None,
+            Key::Down => view.next(),
+            Key::Up => view.previous(),
+            Key::Char('d') => view.cycle_range(),
+            Key::Char('/') => {
+                let query = view.query.clone();
+                self.open_prompt(PromptKind::HistoryQuery, "Search history");
+                if let Some((_, prompt)) = self.prompt.as_mut() {
+                    prompt.input = query;
+                }
+            }
+            Key::Char('\n') => {
+                if let Some(id) = view.selected() {
+                    self.history = None;
+                    self.open_cached(id);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn open_inspector(&mut self) {
+        match self.screens[self.tabs.index].inspected() {
+            Some(item) => self.inspector = Some(Inspector::new(item)),
+            None => self.status = Some("Select a story or comment to inspect first".to_string()),
+        }
+    }
+
+    fn inspector_key(&mut self, key: Key) {
+        let view = match self.inspector.as_mut() {
+            Some(v) => v,
+            None => return,
+        };
+        match key {
+            Key::Esc | Key::Char('i') => self.inspector = None,
+            Key::Down => view.down(),
+            Key::Up => view.up(),
+            Key::PageDown => view.page(true),
+            Key::PageUp => view.page(false),
+            Key::Char('y') | Key::Char('Y') => {
+                let (text, what) = match key {
+                    Key::Char('y') => (view.id().to_string(), format!("id {}", view.id())),
+                    _ => (view.json(), format!("the JSON of item {}", view.id())),
+                };
+                self.status = Some(match copy(text.as_str()) {
+                    Ok(()) => format!("Copied {}", what),
+                    Err(e) => format!("Could not copy: {}", e),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Opens the thread of item `id` from the history, fetching whatever isn't stored
+    fn open_cached(&mut self, id: i64) {
+        match history::cached_root(id) {
+            Some((story, comment)) => {
+                self.screens[self.tabs.index].open(&story, comment);
+                self.opened();
+            }
+            None => self.open_item(id.to_string().as_str()),
+        }
+    }
+
+    /// Opens an item from an id or HN link in the current tab, following comments up to their story
+    fn open_item(&mut self, input: &str) {
+        let id = match parse_item_id(input) {
+            Some(id) => id,
+            None => {
+                self.status = Some(format!("Not an item id or link: {}", input));
+                return;
+            }
+        };
+        match get_root(id.as_str()) {
+            Ok((story, comment)) => {
+                self.screens[self.tabs.index].open(&story, comment);
+                self.opened();
+            }
+            Err(e) => self.status = Some(e.to_string()),
+        }
+    }
+
+    fn inbox_tab(&self) -> Option<usize> {
+        self.inbox.as_ref().map(|_| self.screens.len() - 1)
+    }
+
+    /// Starts checking for new replies and comments on watched stories on worker threads,
+    /// each when its poll interval has passed
+    fn poll(&mut self) {
+        if self.watching.due() {
+            let poll = self.watching.start_poll();
+            self.events.spawn(move || Event::Watched(poll()));
+        }
+        if let Some(inbox) = self.inbox.as_mut().filter(|inbox| inbox.due()) {
+            let poll = inbox.start_poll();
+            self.events.spawn(move || Event::Inbox(poll()));
+        }
+    }
+
+    fn finish_inbox_poll(&mut self, poll: Result<InboxPoll, io::Error>) {
+        let inbox = match self.inbox.as_mut() {
+            Some(inbox) => inbox,
+            None => return,
+        };
+        let (found, items) = match inbox.finish_poll(poll) {
+            Ok(result) => result,
+            Err(e) => {
+                self.status = Some(format!("Could not check for replies: {}", e));
+                return;
+            }
+        };
+        self.screens.last_mut().unwrap().story_list.set_items(items);
+        *self.tabs.titles.last_mut().unwrap() = inbox.title();
+        if found == 0 {
+            return;
+        }
+        self.status = Some(format!("{} new {}", found, if found == 1 { "reply" } else { "replies" }));
+        if inbox.bell() {
+            print!("\x07");
+            let _ = io::stdout().flush();
+        }
+    }
+
+    /// Marks what was just opened in the current tab as read, in the inbox and the watch list
+    fn opened(&mut self) {
+        if let Some(item) = self.screens[self.tabs.index].item.as_ref() {
+            self.watching.mark_seen(item);
+            // Going back through the history isn't a new read
+            if self.tabs.index != self.reads_tab() {
+                self.reads.start(item, self.tabs.titles[self.tabs.index].as_str());
+            }
+            self.refresh_watching();
+            self.refresh_reads();
+        }
+        if self.inbox_tab() != Some(self.tabs.index) {
+            return;
+        }
+        let id = self.screens[self.tabs.index].selected_id();
+        if let (Some(inbox), Some(id)) = (self.inbox.as_mut(), id) {
+            inbox.mark_read(id);
+            *self.tabs.titles.last_mut().unwrap() = inbox.title();
+        }
+    }
+
+    fn refresh_reads(&mut self) {
+        let mut items = Vec::new();
+        let mut titles = Vec::new();
+        let mut details = Vec::new();
+        for (item, title, detail) in self.reads.rows() {
+            items.push(item);
+            titles.push(title);
+            details.push(detail);
+        }
+        let tab = self.reads_tab();
+        let list = &mut self.screens[tab].story_list;
+        list.set_items(items);
+        list.titles = titles;
+        list.details = details;
+    }
+
+    /// Saves the tabs, selections and open threads for the next launch
+    fn save_state(&self) -> Result<(), io::Error> {
+        let mut state = UiState {
+            tab: tab_name(self.tabs.titles[self.tabs.index].as_str()).to_string(),
+            ..UiState::default()
+        };
+        for (title, screen) in self.tabs.titles.iter().zip(self.screens.iter()) {
+            state.screens.insert(tab_name(title).to_string(), screen.state());
+        }
+        state.save()
+    }
+
+    fn restore_state(&mut self, state: &UiState) {
+        for (title, screen) in self.tabs.titles.iter().zip(self.screens.iter_mut()) {
+            if let Some(s) = state.screens.get(tab_name(title)) {
+                screen.restore(s);
+            }
+        }
+        if let Some(i) = self.tabs.titles.iter().position(|t| tab_name(t) == state.tab) {
+            self.tabs.index = i;
+        }
+    }
+
+    fn select_tab(&mut self, index: usize) {
+        if index != self.tabs.index {
+            // Leaving the tab stops the clock on what was being read
+            self.reads.stop();
+            self.tabs.index = index;
+        }
+    }
+
+    /// Starts or stops watching the open story
+    fn toggle_watch(&mut self) {
+        let item = match self.screens[self.tabs.index].item.clone() {
+            Some(item) => item,
+            None => {
+                self.status = Some("Open a story first".to_string());
+                return;
+            }
+        };
+        self.status = Some(match self.watching.toggle(&item) {
+            Ok(true) => format!("Watching {}", str_field(&item, "title")),
+            Ok(false) => format!("Stopped watching {}", str_field(&item, "title")),
+            Err(e) => format!("Could not save the watch list: {}", e),
+        });
+        self.refresh_watching();
+    }
+
+    // Fetches what the current tab will show next on a worker thread
+    fn load_more(&mut self) {
+        let ids = self.screens[self.tabs.index].load_more();
+        if !ids.is_empty() {
+            self.events.spawn(move || Event::Loaded(hn_api::get_item_map(ids)));
+        }
+    }
+
+    // Tells about items that could not be loaded, even after retrying
+    fn report_failures(&mut self) {
+        let failures = hn_api::take_failures();
+        if let Some(last) = failures.last() {
+            self.status = Some(match failures.len() {
+                1 => format!("Could not load {}", last),
+                n => format!("Could not load {} requests, the last: {}", n, last),
+            });
+        }
+    }
+
+    fn finish_watch_poll(&mut self, items: Result<Vec<Item>, io::Error>) {
+        match self.watching.finish_poll(items) {
+            Ok(()) => self.refresh_watching(),
+            Err(e) => self.status = Some(format!("Could not check watched stories: {}", e)),
+        }
+    }
+
+    // Shows the watched stories with `+N` in front of those with new comments
+    fn refresh_watching(&mut self) {
+        let (items, new): (Vec<_>, Vec<_>) = self.watching.rows().into_iter().unzip();
+        let tab = self.watching_tab();
+        let list = &mut self.screens[tab].story_list;
+        list.set_items(items);
+        for (title, n) in list.titles.iter_mut().zip(new) {
+            if n > 0 {
+                *title = format!("+{} {}", n, title);
+            }
+        }
     }
 
     fn down(&mut self) {
@@ -88,29 +950,189 @@ impl App {
         self.screens[self.tabs.index].up()
     }
     fn select(&mut self) {
-        self.screens[self.tabs.index].select()
+        if self.tabs.index == self.reads_tab() {
+            // History rows only hold the id, open the whole story again
+            if let Some(id) = self.screens[self.tabs.index].selected_id() {
+                self.open_cached(id);
+            }
+            return;
+        }
+        match self.screens[self.tabs.index].select() {
+            Ok(()) => self.opened(),
+            Err(e) => self.status = Some(e.to_string()),
+        }
     }
     fn focus(&mut self) { self.screens[self.tabs.index].focus() }
+
+    fn mouse(&mut self, event: MouseEvent) {
+        if let Some(view) = self.inspector.as_mut() {
+            match event {
+                MouseEvent::Press(MouseButton::WheelUp, _, _) => view.up(),
+                MouseEvent::Press(MouseButton::WheelDown, _, _) => view.down(),
+                _ => {}
+            }
+            return;
+        }
+        if let Some(view) = self.history.as_mut() {
+            match event {
+                MouseEvent::Press(MouseButton::WheelUp, _, _) => view.previous(),
+                MouseEvent::Press(MouseButton::WheelDown, _, _) => view.next(),
+                _ => {}
+            }
+            return;
+        }
+        if let Some(view) = self.hiring.as_mut() {
+            match event {
+                MouseEvent::Press(MouseButton::WheelUp, _, _) => view.previous(),
+                MouseEvent::Press(MouseButton::WheelDown, _, _) => view.next(),
+                _ => {}
+            }
+            return;
+        }
+        match event {
+            // termion reports 1-based coordinates
+            MouseEvent::Press(button, x, y) => {
+                let (x, y) = (x.saturating_sub(1), y.saturating_sub(1));
+                match button {
+                    MouseButton::Left => self.click(x, y),
+                    MouseButton::WheelUp => self.screens[self.tabs.index].scroll_at(x, y, false),
+                    MouseButton::WheelDown => self.screens[self.tabs.index].scroll_at(x, y, true),
+                    _ => {}
+                }
+            }
+            MouseEvent::Release(_, _) | MouseEvent::Hold(_, _) => {}
+        }
+    }
+
+    fn click(&mut self, x: u16, y: u16) {
+        let now = Instant::now();
+        let double = match self.last_click {
+            Some((time, last_x, last_y)) => {
+                now.duration_since(time) < DOUBLE_CLICK_INTERVAL && last_x == x && last_y == y
+            }
+            None => false,
+        };
+        // A double click shouldn't chain into a third
+        self.last_click = if double { None } else { Some((now, x, y)) };
+
+        if contains(self.tabs_area, x, y) {
+            if let Some(i) = self.tabs.index_at(self.tabs_area, x) {
+                self.select_tab(i);
+            }
+        } else {
+            if self.screens[self.tabs.index].click(x, y, double) {
+                self.select();
+            }
+        }
+    }
+
+    fn cycle_layout(&mut self) {
+        self.config.layout.mode = self.config.layout.mode.next();
+        self.save_config();
+    }
+
+    fn toggle_zoom(&mut self) {
+        if self.config.layout.mode == LayoutMode::Zoom {
+            self.config.layout.mode = self.unzoomed;
+        } else {
+            self.unzoomed = self.config.layout.mode;
+            self.config.layout.mode = LayoutMode::Zoom;
+        }
+        self.save_config();
+    }
+
+    fn save_config(&self) {
+        // Failing to persist the layout shouldn't interrupt reading
+        let _ = self.config.save();
+    }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
+type HNTerminal = Terminal<TermionBackend<AlternateScreen<MouseTerminal<RawTerminal<Stdout>>>>>;
+
+fn new_terminal() -> Result<HNTerminal, io::Error> {
     let stdout = io::stdout().into_raw_mode()?;
+    let stdout = MouseTerminal::from(stdout);
     let stdout = AlternateScreen::from(stdout);
     let backend = TermionBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
     terminal.hide_cursor()?;
+    Ok(terminal)
+}
 
-    let mut app = App::new();
+fn main() -> Result<(), Box<dyn Error>> {
+    let cli = Cli::parse();
+    let config = Config::load();
+    history::init(&config.history);
+    hn_api::init(&config.network);
+    store::init(&config.cache);
+    if let Some(command) = cli.command {
+        if let Some(e) = config.error.as_ref() {
+            eprintln!("Warning: using the default settings, the config file is invalid: {}", e);
+        }
+        let result = cli::run(command);
+        for failure in hn_api::take_failures() {
+            eprintln!("Warning: could not load {}", failure);
+        }
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return Ok(());
+    }
+
+    let mut terminal = new_terminal()?;
+
+    let mut app = App::new(config);
+    if !cli.fresh {
+        if let Some(state) = UiState::load() {
+            app.restore_state(&state);
+        }
+    }
+    if let Some(item) = cli.open {
+        app.open_item(item.as_str());
+    }
+    if let Some(e) = app.config.error.as_ref() {
+        app.status = Some(format!("Using the default settings, the config file is invalid: {}", e));
+    }
 
     loop {
         terminal.draw(|mut f| { app.draw(&mut f); })?;
-        match app.events.next()? {
+        let event = app.events.next()?;
+        if let Event::Input(_) = event {
+            app.status = None;
+        }
+        let moved = matches!(event, Event::Input(_) | Event::Mouse(_));
+        match event {
+            Event::Input(key) if app.prompt.is_some() => app.prompt_key(key),
+            Event::Input(key) if app.submit_form.is_some() => app.submit_form_key(key),
+            Event::Input(key) if app.inspector.is_some() && key != Key::Char('q') => app.inspector_key(key),
+            Event::Input(key) if app.history.is_some() && key != Key::Char('q') => app.history_key(key),
+            Event::Input(key) if app.hiring.is_some() && key != Key::Char('q') => app.hiring_key(key),
             Event::Input(key) => match key {
                 Key::Char('q') => {
+                    app.reads.stop();
+                    // Not being able to save only means starting fresh next time
+                    let _ = app.save_state();
                     break;
                 }
-                Key::Right => app.tabs.next(),
-                Key::Left => app.tabs.previous(),
+                Key::Right => {
+                    app.reads.stop();
+                    app.tabs.next();
+                }
+                Key::Left => {
+                    app.reads.stop();
+                    app.tabs.previous();
+                }
+                Key::Char('X') if app.tabs.index == app.reads_tab() => {
+                    app.open_prompt(PromptKind::ClearReads, "Clear the reading history? (y/n)");
+                }
+                Key::Char('T') => app.open_prompt(PromptKind::AddTab, ADD_TAB_LABEL),
+                Key::Char('C') => app.confirm_close_tab(),
+                Key::Char('<') => app.move_tab(true),
+                Key::Char('>') => app.move_tab(false),
+                Key::Char('D') => app.open_front_page_picker(),
+                Key::Char('{') => app.step_front_page(-1),
+                Key::Char('}') => app.step_front_page(1),
                 Key::Down => {
                     app.down();
                 }
@@ -123,10 +1145,90 @@ fn main() -> Result<(), Box<dyn Error>> {
                 Key::Char('\x09') => {
                     app.focus();
                 }
+                Key::Char('l') => app.cycle_layout(),
+                Key::Char('z') => app.toggle_zoom(),
+                Key::Char('g') => app.open_prompt(PromptKind::GoToItem, "Go to item"),
+                Key::Char(':') => app.open_prompt(PromptKind::GoToRank, "Go to rank"),
+                Key::PageDown => app.screens[app.tabs.index].page(true),
+                Key::PageUp => app.screens[app.tabs.index].page(false),
+                Key::Home => app.screens[app.tabs.index].jump_to(0),
+                Key::End => app.screens[app.tabs.index].jump_to(usize::MAX),
+                Key::Char('u') => app.act(Action::Upvote),
+                Key::Char('U') => app.act(Action::Unvote),
+                Key::Char('f') => app.act(Action::Favorite),
+                Key::Char('h') => app.act(Action::Hide),
+                Key::Char('r') => app.start_reply(),
+                Key::Char('S') => app.open_submit_form(),
+                Key::Char('w') => app.toggle_watch(),
+                Key::Char('J') => app.open_hiring(),
+                Key::Char('i') => app.open_inspector(),
+                Key::Char('H') => {
+                    app.history = Some(HistoryView::new());
+                    app.open_prompt(PromptKind::HistoryQuery, "Search history");
+                }
+                Key::Char('/') => {
+                    let label = app.config.search.label();
+                    app.open_prompt(PromptKind::Search, label.as_str());
+                }
+                Key::Char('s') => app.cycle_sort(),
+                Key::Char('F') => app.open_filter(),
+                Key::Char('n') => app.next_match(true),
+                Key::Char('N') => app.next_match(false),
+                Key::Char('e') => {
+                    let format = app.config.export.format.name();
+                    app.open_prompt(PromptKind::Export, "Export as markdown, html or json");
+                    if let Some((_, prompt)) = app.prompt.as_mut() {
+                        prompt.input = format.to_string();
+                    }
+                }
+                Key::Char('+') => {
+                    app.config.layout.grow_list();
+                    app.save_config();
+                }
+                Key::Char('-') => {
+                    app.config.layout.shrink_list();
+                    app.save_config();
+                }
+                Key::Char(']') => {
+                    app.config.layout.grow_info();
+                    app.save_config();
+                }
+                Key::Char('[') => {
+                    app.config.layout.shrink_info();
+                    app.save_config();
+                }
 
                 _ => {}
             },
-            _ => {}
+            Event::Mouse(event) => app.mouse(event),
+            Event::Loaded(items) => {
+                for screen in app.screens.iter_mut() {
+                    screen.receive(&items);
+                }
+            }
+            Event::Inbox(poll) => app.finish_inbox_poll(poll),
+            Event::Watched(items) => app.finish_watch_poll(items),
+            Event::Tick => {
+                // Threads keep loading in the background while they are shown
+                app.load_more();
+                app.poll();
+                app.report_failures();
+            }
+        }
+        // Rows the cursor moved to are fetched right away rather than on the next tick
+        if moved {
+            app.load_more();
+        }
+
+        if let Some((parent, initial)) = app.editing.take() {
+            // Give the terminal back to the shell while the editor runs
+            app.events.pause();
+            drop(terminal);
+            let edited = editor::edit(initial.as_str())
+                .map(|text| text.and_then(|t| editor::unquote(t.as_str(), initial.as_str())));
+            terminal = new_terminal()?;
+            app.events.resume();
+            app.finish_reply(parent, edited);
         }
     }
 
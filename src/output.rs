@@ -0,0 +1,251 @@
+use std::collections::HashSet;
+
+use ammonia::Builder;
+use chrono::{TimeZone, Utc};
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use unicode_width::UnicodeWidthStr;
+
+use crate::comment_block::Comment;
+use crate::store::Item;
+
+/// Text formats items can be printed in outside of the TUI
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum Format {
+    Json,
+    Table,
+    Markdown,
+}
+
+pub fn str_field<'a>(item: &'a Map<String, Value>, key: &str) -> &'a str {
+    item.get(key).and_then(|v| v.as_str()).unwrap_or("")
+}
+
+pub fn int_field(item: &Map<String, Value>, key: &str) -> i64 {
+    item.get(key).and_then(|v| v.as_i64()).unwrap_or(0)
+}
+
+/// Converts the HTML the API returns for `text` and `about` fields to plain text
+pub fn plain_text(html: &str) -> String {
+    let stripped = Builder::new()
+        .tags(HashSet::new())
+        .clean(html.replace("<p>", "\n\n").as_str())
+        .to_string();
+    // ammonia escapes what is left, undo it since the output isn't HTML
+    stripped
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&#x2F;", "/")
+        .replace("&amp;", "&")
+}
+
+/// Host of `url` without a leading `www.`, e.g. `github.com`
+pub fn domain(url: &str) -> Option<String> {
+    let rest = url.split_once("://").map(|(_, rest)| rest)?;
+    let host = rest.split(['/', '?', '#', ':']).next().unwrap_or("");
+    let host = host.strip_prefix("www.").unwrap_or(host);
+    if host.is_empty() { None } else { Some(host.to_lowercase()) }
+}
+
+/// Formats a unix timestamp from the API as an absolute UTC date
+pub fn date(time: i64) -> String {
+    match Utc.timestamp_opt(time, 0).single() {
+        Some(d) => d.format("%Y-%m-%d %H:%M UTC").to_string(),
+        None => "".to_string(),
+    }
+}
+
+fn json(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_default()
+}
+
+// Left aligned columns separated by two spaces, with an optional header row
+fn table(header: Option<&[&str]>, rows: &[Vec<String>]) -> String {
+    let n_columns = header.map_or_else(|| rows.first().map_or(0, |r| r.len()), |h| h.len());
+    let mut widths: Vec<usize> = vec![0; n_columns];
+    for (i, h) in header.unwrap_or(&[]).iter().enumerate() {
+        widths[i] = h.width();
+    }
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.width());
+        }
+    }
+    let format_row = |cells: Vec<&str>| -> String {
+        let padded: Vec<String> = cells.iter().enumerate().map(|(i, c)| {
+            format!("{}{}", c, " ".repeat(widths[i] - c.width()))
+        }).collect();
+        padded.join("  ").trim_end().to_string()
+    };
+
+    let mut lines = Vec::new();
+    if let Some(h) = header {
+        lines.push(format_row(h.to_vec()));
+    }
+    for row in rows {
+        lines.push(format_row(row.iter().map(|c| c.as_str()).collect()));
+    }
+    lines.join("\n")
+}
+
+/// A feed of stories, ranked in the order given
+pub fn stories(items: &[Item], format: Format) -> String {
+    match format {
+        Format::Json => json(&Value::Array(items.iter().map(|item| Value::Object(Map::clone(item))).collect())),
+        Format::Table => {
+            let rows: Vec<Vec<String>> = items.iter().enumerate().map(|(i, item)| {
+                vec![
+                    format!("{}.", i + 1),
+                    int_field(item, "score").to_string(),
+                    int_field(item, "descendants").to_string(),
+                    str_field(item, "title").to_string(),
+                    str_field(item, "by").to_string(),
+                ]
+            }).collect();
+            table(Some(&["#", "Points", "Comments", "Title", "Author"]), rows.as_slice())
+        }
+        Format::Markdown => {
+            let lines: Vec<String> = items.iter().enumerate().map(|(i, item)| {
+                format!("{}. {} ({} points by {} | {} comments)",
+                        i + 1, story_link(item), int_field(item, "score"),
+                        str_field(item, "by"), int_field(item, "descendants"))
+            }).collect();
+            lines.join("\n")
+        }
+    }
+}
+
+// Markdown link to the story's URL, or to its HN page for text posts
+fn story_link(item: &Map<String, Value>) -> String {
+    let url = match str_field(item, "url") {
+        "" => format!("https://news.ycombinator.com/item?id={}", int_field(item, "id")),
+        url => url.to_string(),
+    };
+    format!("[{}]({})", str_field(item, "title"), url)
+}
+
+/// A single item, followed by its comment tree when `comments` is given
+pub fn thread(item: &Map<String, Value>, comments: Option<&[Comment]>, format: Format) -> String {
+    match format {
+        Format::Json => {
+            let mut item = item.clone();
+            if let Some(c) = comments {
+                item.insert("comments".to_string(), serde_json::to_value(c).unwrap_or(Value::Null));
+            }
+            json(&Value::Object(item))
+        }
+        Format::Table => {
+            let mut lines = vec![
+                format!("{} ({})", str_field(item, "title"), str_field(item, "url")),
+                format!("{} points by {} at {} | {} comments", int_field(item, "score"),
+                        str_field(item, "by"), date(int_field(item, "time")),
+                        int_field(item, "descendants")),
+            ];
+            let text = plain_text(str_field(item, "text"));
+            if !text.is_empty() {
+                lines.push(String::new());
+                lines.push(text);
+            }
+            if let Some(comments) = comments {
+                for c in comments {
+                    comment_table(c, 0, &mut lines);
+                }
+            }
+            lines.join("\n")
+        }
+        Format::Markdown => {
+            let mut lines = vec![
+                format!("# {}", story_link(item)),
+                String::new(),
+                format!("*{} points by {} at {} | {} comments*", int_field(item, "score"),
+                        str_field(item, "by"), date(int_field(item, "time")),
+                        int_field(item, "descendants")),
+            ];
+            let text = plain_text(str_field(item, "text"));
+            if !text.is_empty() {
+                lines.push(String::new());
+                lines.push(text);
+            }
+            if let Some(comments) = comments {
+                lines.push(String::new());
+                for c in comments {
+                    comment_markdown(c, 1, &mut lines);
+                }
+            }
+            lines.join("\n")
+        }
+    }
+}
+
+fn comment_table(c: &Comment, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    lines.push(String::new());
+    lines.push(format!("{}{} at {}", indent, c.author, date(c.time)));
+    for line in plain_text(c.text.as_str()).lines() {
+        lines.push(format!("{}{}", indent, line));
+    }
+    if let Some(replies) = &c.replies {
+        for reply in replies {
+            comment_table(reply, depth + 1, lines);
+        }
+    }
+}
+
+// Each level of the tree is one more level of blockquote
+fn comment_markdown(c: &Comment, depth: usize, lines: &mut Vec<String>) {
+    let quote = "> ".repeat(depth);
+    lines.push(quote.trim_end().to_string());
+    lines.push(format!("{}**{}** at {}", quote, c.author, date(c.time)));
+    lines.push(quote.trim_end().to_string());
+    for line in plain_text(c.text.as_str()).lines() {
+        lines.push(format!("{}{}", quote, line).trim_end().to_string());
+    }
+    if let Some(replies) = &c.replies {
+        for reply in replies {
+            comment_markdown(reply, depth + 1, lines);
+        }
+    }
+    if depth == 1 {
+        lines.push(String::new());
+    }
+}
+
+/// A user's profile
+pub fn user(user: &Map<String, Value>, format: Format) -> String {
+    let n_submitted = user.get("submitted").and_then(|s| s.as_array()).map_or(0, |s| s.len());
+    match format {
+        Format::Json => json(&Value::Object(user.clone())),
+        Format::Table => {
+            let rows = vec![
+                vec!["user".to_string(), str_field(user, "id").to_string()],
+                vec!["created".to_string(), date(int_field(user, "created"))],
+                vec!["karma".to_string(), int_field(user, "karma").to_string()],
+                vec!["submitted".to_string(), n_submitted.to_string()],
+            ];
+            let mut out = table(None, rows.as_slice());
+            let about = plain_text(str_field(user, "about"));
+            if !about.is_empty() {
+                out = format!("{}\n\n{}", out, about);
+            }
+            out
+        }
+        Format::Markdown => {
+            let mut lines = vec![
+                format!("# {}", str_field(user, "id")),
+                String::new(),
+                format!("- **Created:** {}", date(int_field(user, "created"))),
+                format!("- **Karma:** {}", int_field(user, "karma")),
+                format!("- **Submissions:** {}", n_submitted),
+            ];
+            let about = plain_text(str_field(user, "about"));
+            if !about.is_empty() {
+                lines.push(String::new());
+                lines.push(about);
+            }
+            lines.join("\n")
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use termion::event::Key;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::Rect;
+use tui::widgets::{Paragraph, Text};
+
+use crate::colors::{get_style, HNStyles};
+
+/// What a key press did to the prompt
+pub enum PromptResult {
+    Editing,
+    Submit(String),
+    Cancel,
+}
+
+/// A single line text input drawn over the bottom of the screen
+pub struct Prompt {
+    pub label: String,
+    pub input: String,
+}
+
+impl Prompt {
+    pub fn new(label: &str) -> Prompt {
+        Prompt {
+            label: label.to_string(),
+            input: String::new(),
+        }
+    }
+
+    pub fn key(&mut self, key: Key) -> PromptResult {
+        match key {
+            Key::Char('\n') => PromptResult::Submit(self.input.clone()),
+            Key::Esc => PromptResult::Cancel,
+            Key::Backspace => {
+                self.input.pop();
+                PromptResult::Editing
+            }
+            Key::Char(c) => {
+                self.input.push(c);
+                PromptResult::Editing
+            }
+            _ => PromptResult::Editing,
+        }
+    }
+
+    pub fn draw<B: Backend>(&self, f: &mut Frame<B>, chunk: Rect) {
+        let text = [Text::raw(format!("{}: {}_", self.label, self.input))];
+        let paragraph = Paragraph::new(text.iter())
+            .style(get_style(HNStyles::OrangeBlock));
+        f.render_widget(paragraph, chunk);
+    }
+}
@@ -0,0 +1,152 @@
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::output::{int_field, str_field};
+use crate::store::Item;
+
+const READS_DIR: &str = "hn_terminal";
+const READS_FILE: &str = "reads.json";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReadingConfig {
+    // Days reads are kept for, 0 keeps them forever
+    pub retention_days: u32,
+}
+
+/// A story opened from one of the tabs
+#[derive(Clone, Serialize, Deserialize)]
+struct Read {
+    id: i64,
+    title: String,
+    // Tab the story was opened from
+    feed: String,
+    // Unix time it was opened at
+    opened: i64,
+    // Time spent on it before something else was opened
+    seconds: u64,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn duration(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 60 * 60 => format!("{}m", s / 60),
+        s => format!("{}h{:02}m", s / (60 * 60), s / 60 % 60),
+    }
+}
+
+/// Stories read so far, newest first, kept between runs
+pub struct ReadingLog {
+    config: ReadingConfig,
+    reads: Vec<Read>,
+    // When the newest read was opened, while it is still being read
+    reading_since: Option<Instant>,
+}
+
+impl ReadingLog {
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(READS_DIR).join(READS_FILE))
+    }
+
+    /// Loads the stored reads, dropping those past the retention period
+    pub fn load(config: &ReadingConfig) -> ReadingLog {
+        let reads = ReadingLog::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(contents.as_str()).ok())
+            .unwrap_or_default();
+        let mut log = ReadingLog {
+            config: config.clone(),
+            reads,
+            reading_since: None,
+        };
+        log.prune();
+        log
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let path = match ReadingLog::path() {
+            Some(p) => p,
+            None => return Err(Error::new(ErrorKind::NotFound, "Could not find data directory")),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string(&self.reads)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        fs::write(path, contents)
+    }
+
+    fn prune(&mut self) {
+        if self.config.retention_days > 0 {
+            let oldest = now() - self.config.retention_days as i64 * 24 * 60 * 60;
+            self.reads.retain(|r| r.opened >= oldest);
+        }
+    }
+
+    /// Records that the story `item` was opened from the tab `feed`
+    pub fn start(&mut self, item: &Map<String, Value>, feed: &str) {
+        self.stop();
+        self.reads.insert(0, Read {
+            id: int_field(item, "id"),
+            title: str_field(item, "title").to_string(),
+            feed: feed.to_string(),
+            opened: now(),
+            seconds: 0,
+        });
+        self.reading_since = Some(Instant::now());
+        self.prune();
+        // Losing a read isn't worth interrupting anything for
+        let _ = self.save();
+    }
+
+    /// Adds the time since the newest read was opened to it
+    pub fn stop(&mut self) {
+        if let (Some(since), Some(read)) = (self.reading_since.take(), self.reads.first_mut()) {
+            read.seconds += since.elapsed().as_secs();
+            let _ = self.save();
+        }
+    }
+
+    pub fn clear(&mut self) -> Result<(), Error> {
+        self.reads.clear();
+        self.reading_since = None;
+        self.save()
+    }
+
+    /// The reads as list items with a title and a details line each, the date only shown on
+    /// the first row of a day
+    pub fn rows(&self) -> Vec<(Item, String, String)> {
+        let mut last_day = String::new();
+        self.reads.iter().map(|r| {
+            let opened = match Local.timestamp_opt(r.opened, 0).single() {
+                Some(t) => t,
+                None => Local.timestamp_opt(0, 0).unwrap(),
+            };
+            let day = opened.format("%a %Y-%m-%d").to_string();
+            let shown_day = if day == last_day { " ".repeat(day.len()) } else { day.clone() };
+            last_day = day;
+            let title = format!("{}  {}", shown_day, r.title);
+            let details = format!("{}  {} from {}, read for {}", " ".repeat(shown_day.len()), opened.format("%H:%M"), r.feed, duration(r.seconds));
+
+            let mut item = Map::new();
+            item.insert("id".to_string(), Value::from(r.id));
+            item.insert("type".to_string(), Value::from("story"));
+            item.insert("title".to_string(), Value::from(r.title.clone()));
+            (Arc::new(item), title, details)
+        }).collect()
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::{Error, ErrorKind};
+
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    // Treat the query as a regular expression instead of plain text
+    pub regex: bool,
+    pub ignore_case: bool,
+}
+
+impl Default for SearchConfig {
+    fn default() -> SearchConfig {
+        SearchConfig {
+            regex: false,
+            ignore_case: true,
+        }
+    }
+}
+
+impl SearchConfig {
+    /// Label of the search prompt, naming the active modes
+    pub fn label(&self) -> String {
+        let mut modes = Vec::new();
+        if self.regex {
+            modes.push("regex");
+        }
+        if self.ignore_case {
+            modes.push("ignore case");
+        }
+        if modes.is_empty() {
+            "Search".to_string()
+        } else {
+            format!("Search ({})", modes.join(", "))
+        }
+    }
+
+    /// Compiles `query` for the active modes, `None` when it is empty
+    pub fn pattern(&self, query: &str) -> Result<Option<Regex>, Error> {
+        if query.is_empty() {
+            return Ok(None);
+        }
+        let query = if self.regex { query.to_string() } else { regex::escape(query) };
+        RegexBuilder::new(query.as_str())
+            .case_insensitive(self.ignore_case)
+            .build()
+            .map(Some)
+            .map_err(|e| Error::new(ErrorKind::InvalidInput, e.to_string()))
+    }
+}
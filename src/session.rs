@@ -0,0 +1,540 @@
+use std::fs::{self, OpenOptions};
+use std::io::{Error, ErrorKind, Write};
+use std::path::PathBuf;
+
+use reqwest::blocking::{Client, Response};
+use reqwest::header::{COOKIE, LOCATION, SET_COOKIE};
+use reqwest::redirect::Policy;
+use serde::{Deserialize, Serialize};
+
+use crate::output::plain_text;
+
+const SESSION_DIR: &str = "hn_terminal";
+const SESSION_FILE: &str = "session";
+const MAX_ERROR_LENGTH: usize = 200;
+const MAX_TITLE_LENGTH: usize = 80;
+// Only on pages seen by a logged in user
+const LOGOUT_LINK: &str = "href=\"logout";
+
+const TITLE_TOO_LONG: &str = "The title is too long, HN allows at most 80 characters";
+
+// Messages HN shows on a rejected submission, with a readable explanation
+const SUBMIT_ERRORS: [(&str, &str); 5] = [
+    ("submitting too fast", "Rate limited, HN says you're submitting too fast. Try again later"),
+    ("too long", TITLE_TOO_LONG),
+    ("Please try again", "HN rejected the form, the submission may have expired. Try again"),
+    ("both a url and text", "Submit either a URL or text, not both"),
+    ("banned", "This site is banned on HN"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    // HN web site to log into, can point at a local server for testing
+    pub host: String,
+    pub username: Option<String>,
+    // Read from the system keyring when not set and built with the `keyring` feature
+    pub password: Option<String>,
+}
+
+impl Default for AuthConfig {
+    fn default() -> AuthConfig {
+        AuthConfig {
+            host: "https://news.ycombinator.com".to_string(),
+            username: None,
+            password: None,
+        }
+    }
+}
+
+/// Actions on a story or comment that only need the item's auth token
+#[derive(Clone, Copy)]
+pub enum Action {
+    Upvote,
+    Unvote,
+    Favorite,
+    Hide,
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Upvote => "Upvoted",
+            Action::Unvote => "Unvoted",
+            Action::Favorite => "Favorited",
+            Action::Hide => "Hidden",
+        }
+    }
+
+    // Start of the link to the action on the item's page, e.g. `vote?id=1&amp;how=up`.
+    // The `auth` token is somewhere among the parameters that follow
+    fn link(self, id: i64) -> String {
+        match self {
+            Action::Upvote => format!("vote?id={}&amp;how=up", id),
+            Action::Unvote => format!("vote?id={}&amp;how=un", id),
+            Action::Favorite => format!("fave?id={}", id),
+            Action::Hide => format!("hide?id={}", id),
+        }
+    }
+}
+
+fn not_connected() -> Error {
+    Error::new(ErrorKind::NotConnected, "Could not access HackerNews")
+}
+
+// Value of the hidden form field `name` on an HN page
+fn hidden_input(page: &str, name: &str) -> Option<String> {
+    let field = format!("name=\"{}\" value=\"", name);
+    let start = page.find(field.as_str())? + field.len();
+    page[start..].split('"').next().map(|v| v.to_string())
+}
+
+// HN reports problems as a plain page of text, e.g. "You're posting too fast"
+fn response_error(body: &str) -> Error {
+    let message = plain_text(body);
+    let message = message.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if message.is_empty() {
+        Error::other("HN rejected the request")
+    } else {
+        Error::other(message.chars().take(MAX_ERROR_LENGTH).collect::<String>())
+    }
+}
+
+#[cfg(feature = "keyring")]
+fn keyring_password(username: &str) -> Option<String> {
+    keyring::Entry::new("hn_terminal", username).ok()?.get_password().ok()
+}
+
+#[cfg(not(feature = "keyring"))]
+fn keyring_password(_username: &str) -> Option<String> {
+    None
+}
+
+/// A logged in session on the HN web site, used for everything the read-only API can't do
+pub struct Session {
+    client: Client,
+    config: AuthConfig,
+    host: String,
+    pub username: String,
+    // Value of the `user` cookie HN sets on login
+    cookie: String,
+}
+
+impl Session {
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(SESSION_DIR).join(SESSION_FILE))
+    }
+
+    // Session cookie saved by a previous login for the same host and user
+    fn stored_cookie(host: &str, username: &str) -> Option<String> {
+        let contents = fs::read_to_string(Session::path()?).ok()?;
+        let mut lines = contents.lines();
+        if lines.next()? == host && lines.next()? == username {
+            lines.next().map(|c| c.to_string())
+        } else {
+            None
+        }
+    }
+
+    fn store_cookie(&self) -> Result<(), Error> {
+        let path = match Session::path() {
+            Some(p) => p,
+            None => return Err(Error::new(ErrorKind::NotFound, "Could not find data directory")),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        // Only the user may read the cookie, from the moment the file exists
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path)?;
+        file.write_all(format!("{}\n{}\n{}\n", self.host, self.username, self.cookie).as_bytes())
+    }
+
+    fn forget_cookie() {
+        if let Some(path) = Session::path() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Reuses the stored session cookie for the configured user, or logs in with their password
+    pub fn login(config: &AuthConfig) -> Result<Session, Error> {
+        let username = match config.username.as_ref() {
+            Some(u) => u.clone(),
+            None => return Err(Error::new(ErrorKind::InvalidInput, "No username set in the [auth] config")),
+        };
+        let host = config.host.trim_end_matches('/').to_string();
+        // Redirects are followed by hand so the cookie set on them isn't lost
+        let client = Client::builder()
+            .redirect(Policy::none())
+            .build()
+            .map_err(|_| not_connected())?;
+
+        let mut session = Session { client, config: config.clone(), host, username, cookie: String::new() };
+        match Session::stored_cookie(session.host.as_str(), session.username.as_str()) {
+            Some(cookie) => session.cookie = cookie,
+            None => session.log_in()?,
+        }
+        Ok(session)
+    }
+
+    // Logs in with the user's password for a new session cookie
+    fn log_in(&mut self) -> Result<(), Error> {
+        let password = match self.config.password.clone().or_else(|| keyring_password(self.username.as_str())) {
+            Some(p) => p,
+            None => return Err(Error::new(ErrorKind::InvalidInput, "No password in the config or keyring")),
+        };
+        let resp = self.client.post(format!("{}/login", self.host).as_str())
+            .form(&[("acct", self.username.as_str()), ("pw", password.as_str()), ("goto", "news")])
+            .send()
+            .map_err(|_| not_connected())?;
+        let cookie = resp.headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|h| h.to_str().ok())
+            .find(|h| h.starts_with("user="))
+            .and_then(|h| h.split(';').next())
+            .map(|c| c.trim_start_matches("user=").to_string());
+        self.cookie = match cookie {
+            Some(c) if !c.is_empty() => c,
+            _ => return Err(Error::new(ErrorKind::PermissionDenied, "Bad login")),
+        };
+        // Not being able to save the cookie only means logging in again next time
+        let _ = self.store_cookie();
+        Ok(())
+    }
+
+    fn get(&self, path: &str) -> Result<Response, Error> {
+        self.client.get(format!("{}/{}", self.host, path).as_str())
+            .header(COOKIE, format!("user={}", self.cookie))
+            .send()
+            .map_err(|_| not_connected())
+    }
+
+    fn post(&self, path: &str, form: &[(&str, &str)]) -> Result<Response, Error> {
+        self.client.post(format!("{}/{}", self.host, path).as_str())
+            .header(COOKIE, format!("user={}", self.cookie))
+            .form(form)
+            .send()
+            .map_err(|_| not_connected())
+    }
+
+    // HTML of a page on the site, or `None` when HN doesn't see the user as logged in
+    fn try_page(&self, path: &str) -> Result<Option<String>, Error> {
+        let resp = self.get(path)?;
+        if resp.status().is_redirection() {
+            return Ok(None);
+        }
+        let page = resp.text().map_err(|_| not_connected())?;
+        Ok(if page.contains(LOGOUT_LINK) { Some(page) } else { None })
+    }
+
+    /// HTML of a page on the site, as seen by the logged in user. An expired session cookie is
+    /// dropped and the user logged in again once
+    fn page(&mut self, path: &str) -> Result<String, Error> {
+        if let Some(page) = self.try_page(path)? {
+            return Ok(page);
+        }
+        Session::forget_cookie();
+        self.log_in()?;
+        match self.try_page(path)? {
+            Some(page) => Ok(page),
+            None => Err(Error::new(ErrorKind::PermissionDenied, "Session expired, log in again")),
+        }
+    }
+
+    /// Finds the auth token of `action` for item `id` on the item's page
+    fn auth_token(&mut self, action: Action, id: i64) -> Result<String, Error> {
+        let page = self.page(format!("item?id={}", id).as_str())?;
+        let link = action.link(id);
+        let href = page.find(format!("{}&amp;", link).as_str())
+            .map(|i| &page[i..])
+            .and_then(|href| href.split(['\'', '"']).next());
+        let token = href
+            .and_then(|href| href.find("auth=").map(|i| &href[i + 5..]))
+            .map(|t| t.chars().take_while(|c| c.is_ascii_alphanumeric()).collect::<String>());
+        match token {
+            Some(t) if !t.is_empty() => Ok(t),
+            _ => Err(Error::new(ErrorKind::NotFound,
+                                format!("Item {} can't be {} from this account", id, action.name().to_lowercase()))),
+        }
+    }
+
+    /// Posts `text` as a reply to the story or comment `parent`. Returns the id of the new
+    /// comment when HN's redirect points at it
+    pub fn reply(&mut self, parent: i64, text: &str) -> Result<Option<i64>, Error> {
+        let page = self.page(format!("item?id={}", parent).as_str())?;
+        let hmac = match hidden_input(page.as_str(), "hmac") {
+            Some(h) => h,
+            None => return Err(Error::new(ErrorKind::NotFound, format!("Item {} can't be replied to", parent))),
+        };
+        let parent = parent.to_string();
+        let goto = format!("item?id={}", parent);
+        let resp = self.post("comment", &[
+            ("parent", parent.as_str()), ("goto", goto.as_str()), ("hmac", hmac.as_str()), ("text", text)
+        ])?;
+        // Accepted posts redirect back to the thread, e.g. `item?id=1#12345`, anything else is
+        // an error page
+        if resp.status().is_redirection() {
+            let location = resp.headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .unwrap_or("");
+            Ok(location.rsplit_once('#').and_then(|(_, id)| id.parse().ok()))
+        } else {
+            Err(response_error(resp.text().unwrap_or_default().as_str()))
+        }
+    }
+
+    /// Submits a story with either a `url` or a `text`. A duplicate URL is reported as an
+    /// `AlreadyExists` error naming the story that already has it
+    pub fn submit(&mut self, title: &str, url: &str, text: &str) -> Result<(), Error> {
+        let title = title.trim();
+        if title.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "A title is required"));
+        }
+        if title.chars().count() > MAX_TITLE_LENGTH {
+            return Err(Error::new(ErrorKind::InvalidInput, TITLE_TOO_LONG));
+        }
+        if url.trim().is_empty() && text.trim().is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "Either a URL or text is required"));
+        }
+
+        let page = self.page("submit")?;
+        let fnid = match hidden_input(page.as_str(), "fnid") {
+            Some(f) => f,
+            None => return Err(Error::new(ErrorKind::PermissionDenied, "Could not open the submit form, log in again")),
+        };
+        let resp = self.post("r", &[
+            ("fnid", fnid.as_str()), ("fnop", "submit-page"), ("title", title), ("url", url.trim()), ("text", text)
+        ])?;
+
+        if resp.status().is_redirection() {
+            let location = resp.headers()
+                .get(LOCATION)
+                .and_then(|l| l.to_str().ok())
+                .unwrap_or("");
+            // Duplicates are sent to the story that already has the URL
+            return match location.find("item?id=") {
+                Some(i) => Err(Error::new(ErrorKind::AlreadyExists,
+                                          format!("Duplicate URL, already submitted as item {}", &location[i + 8..]))),
+                None => Ok(()),
+            };
+        }
+        let body = resp.text().unwrap_or_default();
+        match SUBMIT_ERRORS.iter().find(|(pattern, _)| body.contains(pattern)) {
+            Some((_, message)) => Err(Error::other(*message)),
+            None => Err(response_error(body.as_str())),
+        }
+    }
+
+    /// Upvotes, unvotes, favorites or hides the story or comment `id`
+    pub fn act(&mut self, action: Action, id: i64) -> Result<(), Error> {
+        let token = self.auth_token(action, id)?;
+        let path = format!("{}&auth={}", action.link(id).replace("&amp;", "&"), token);
+        let resp = self.get(path.as_str())?;
+        let status = resp.status();
+        let body = resp.text().unwrap_or_default();
+        if !(status.is_success() || status.is_redirection()) || body.contains("Unknown or expired link") {
+            return Err(Error::other(format!("HN rejected the request ({})", status)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Mutex, MutexGuard};
+    use crate::test_server::{Request, Response, TestServer};
+
+    // Every session shares the cookie file, so tests using it take turns
+    static COOKIE_FILE: Mutex<()> = Mutex::new(());
+
+    const ITEM_PAGE: &str = r#"<a id='logout' rel='nofollow' href="logout?auth=f00&amp;goto=item%3Fid%3D5">logout</a>
+<a id='up_5' href='vote?id=5&amp;how=up&amp;auth=abc123&amp;goto=item%3Fid%3D5'></a>
+<a href="hide?id=5&amp;auth=def456&amp;goto=item%3Fid%3D5">hide</a>"#;
+
+    // Keeps the cookie file of the tests out of the user's data directory
+    fn cookie_file() -> MutexGuard<'static, ()> {
+        let guard = COOKIE_FILE.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::set_var("XDG_DATA_HOME", std::env::temp_dir().join(format!("hn_terminal-test-{}", std::process::id())));
+        guard
+    }
+
+    fn config(server: &TestServer) -> AuthConfig {
+        AuthConfig {
+            host: server.url.clone(),
+            username: Some("alice".to_string()),
+            password: Some("secret".to_string()),
+        }
+    }
+
+    // A site where logging in gives the cookie `new` and item 5 can be voted on and hidden
+    fn site(request: &Request, _: usize) -> Response {
+        let logged_in = request.header("cookie") == Some("user=new");
+        match request.path.split('?').next().unwrap_or("") {
+            "/login" => Response::new(302, "").header("Set-Cookie", "user=new; Path=/").header("Location", "news"),
+            "/item" if logged_in => Response::new(200, ITEM_PAGE),
+            "/item" => Response::new(200, "<a href=\"login?goto=news\">login</a>"),
+            "/vote" | "/hide" => Response::new(302, "").header("Location", "item?id=5"),
+            _ => Response::new(404, "Unknown."),
+        }
+    }
+
+    fn paths(server: &TestServer) -> Vec<String> {
+        server.requests().iter().map(|r| r.path.clone()).collect()
+    }
+
+    #[test]
+    fn logs_in_and_reuses_the_stored_cookie() {
+        let _file = cookie_file();
+        let server = TestServer::start(site);
+        let session = Session::login(&config(&server)).unwrap();
+        assert_eq!(session.cookie, "new");
+        let requests = server.requests();
+        assert_eq!(requests[0].method, "POST");
+        assert!(requests[0].body.contains("acct=alice") && requests[0].body.contains("pw=secret"));
+
+        let session = Session::login(&config(&server)).unwrap();
+        assert_eq!(session.cookie, "new");
+        assert_eq!(server.requests().len(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(Session::path().unwrap()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn rejects_a_bad_login() {
+        let _file = cookie_file();
+        let server = TestServer::start(|_, _| Response::new(200, "Bad login."));
+        let mut config = config(&server);
+        config.username = Some("bob".to_string());
+        assert_eq!(Session::login(&config).err().map(|e| e.kind()), Some(ErrorKind::PermissionDenied));
+    }
+
+    #[test]
+    fn finds_auth_tokens() {
+        let _file = cookie_file();
+        let server = TestServer::start(site);
+        let mut session = Session::login(&config(&server)).unwrap();
+        assert_eq!(session.auth_token(Action::Upvote, 5).unwrap(), "abc123");
+        assert_eq!(session.auth_token(Action::Hide, 5).unwrap(), "def456");
+        assert_eq!(session.auth_token(Action::Favorite, 5).unwrap_err().kind(), ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn acts_with_the_auth_token() {
+        let _file = cookie_file();
+        let server = TestServer::start(site);
+        let mut session = Session::login(&config(&server)).unwrap();
+        session.act(Action::Upvote, 5).unwrap();
+        assert_eq!(paths(&server), ["/login", "/item?id=5", "/vote?id=5&how=up&auth=abc123"]);
+        assert_eq!(server.requests()[2].header("cookie"), Some("user=new"));
+    }
+
+    #[test]
+    fn reports_rejected_actions() {
+        let _file = cookie_file();
+        let server = TestServer::start(|request, n| match request.path.as_str() {
+            "/item?id=5" => Response::new(200, ITEM_PAGE),
+            _ if n == 0 => site(request, n),
+            _ => Response::new(200, "Unknown or expired link."),
+        });
+        let mut session = Session::login(&config(&server)).unwrap();
+        assert!(session.act(Action::Hide, 5).is_err());
+    }
+
+    #[test]
+    fn logs_in_again_once_the_cookie_expired() {
+        let _file = cookie_file();
+        let server = TestServer::start(site);
+        let host = server.url.trim_end_matches('/');
+        let path = Session::path().unwrap();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, format!("{}\nalice\nold\n", host)).unwrap();
+
+        let mut session = Session::login(&config(&server)).unwrap();
+        assert_eq!(session.cookie, "old");
+        session.act(Action::Upvote, 5).unwrap();
+        assert_eq!(paths(&server), ["/item?id=5", "/login", "/item?id=5", "/vote?id=5&how=up&auth=abc123"]);
+        assert_eq!(Session::stored_cookie(host, "alice").as_deref(), Some("new"));
+    }
+
+    #[test]
+    fn gives_up_when_logging_in_again_does_not_help() {
+        let _file = cookie_file();
+        let server = TestServer::start(|request, n| match request.path.as_str() {
+            "/login" => site(request, n),
+            _ => Response::new(302, "").header("Location", "login"),
+        });
+        let mut session = Session::login(&config(&server)).unwrap();
+        assert_eq!(session.act(Action::Upvote, 5).unwrap_err().kind(), ErrorKind::PermissionDenied);
+        assert_eq!(paths(&server), ["/login", "/item?id=5", "/login", "/item?id=5"]);
+    }
+
+    // A site taking submissions with `answer`, after the login and the submit form
+    fn submit_site<F>(answer: F) -> TestServer where F: Fn() -> Response + Send + Sync + 'static {
+        TestServer::start(move |request, n| match request.path.as_str() {
+            "/login" => site(request, n),
+            "/submit" => Response::new(200, r#"<a id='logout' href="logout?auth=f00">logout</a>
+<input type="hidden" name="fnid" value="xyz">"#),
+            _ => answer(),
+        })
+    }
+
+    #[test]
+    fn submits_stories() {
+        let _file = cookie_file();
+        let server = submit_site(|| Response::new(302, "").header("Location", "newest"));
+        let mut session = Session::login(&config(&server)).unwrap();
+        session.submit(" Show HN: A terminal client ", "https://example.com", "").unwrap();
+        let post = server.requests().pop().unwrap();
+        assert_eq!(post.path, "/r");
+        assert!(post.body.starts_with("fnid=xyz&fnop=submit-page&title=Show+HN%3A+A+terminal+client&url=https"));
+    }
+
+    #[test]
+    fn explains_rejected_submissions() {
+        let _file = cookie_file();
+        // Status, redirect and page of the answer, then the error it should give
+        let answers = [
+            (302, "item?id=42", "", ErrorKind::AlreadyExists, "Duplicate URL, already submitted as item 42"),
+            (200, "", "You're submitting too fast. Please slow down. Thanks.", ErrorKind::Other, SUBMIT_ERRORS[0].1),
+            (200, "", "That title is too long.", ErrorKind::Other, TITLE_TOO_LONG),
+            (200, "", "Please try again.", ErrorKind::Other, SUBMIT_ERRORS[2].1),
+            (200, "", "<b>Something   else</b>", ErrorKind::Other, "Something else"),
+        ];
+        for &(status, location, body, kind, message) in answers.iter() {
+            let server = submit_site(move || Response::new(status, body).header("Location", location));
+            let mut session = Session::login(&config(&server)).unwrap();
+            let e = session.submit("A title", "https://example.com", "").unwrap_err();
+            assert_eq!((e.kind(), e.to_string().as_str()), (kind, message));
+        }
+    }
+
+    #[test]
+    fn checks_submissions_before_sending_them() {
+        let _file = cookie_file();
+        let server = submit_site(|| Response::new(500, ""));
+        let mut session = Session::login(&config(&server)).unwrap();
+        let long = "x".repeat(MAX_TITLE_LENGTH + 1);
+        for (title, url, text, message) in [
+            ("", "https://example.com", "", "A title is required"),
+            (long.as_str(), "https://example.com", "", TITLE_TOO_LONG),
+            ("A title", " ", "", "Either a URL or text is required"),
+        ] {
+            let e = session.submit(title, url, text).unwrap_err();
+            assert_eq!((e.kind(), e.to_string().as_str()), (ErrorKind::InvalidInput, message));
+        }
+        assert_eq!(paths(&server), ["/login"]);
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::story_screen::Focus;
+
+const STATE_DIR: &str = "hn_terminal";
+const STATE_FILE: &str = "state.json";
+
+/// Where one tab was left
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScreenState {
+    // Story selected in the list
+    pub selected: Option<i64>,
+    // Story open in the info and comment panes
+    pub story: Option<i64>,
+    // Highlighted comment of the open story
+    pub comment: Option<i64>,
+    pub comment_scroll: usize,
+    pub focus: Focus,
+}
+
+/// What was on screen at exit, restored on the next launch. Tabs are keyed by their name
+/// since the optional tabs can come and go between runs
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UiState {
+    pub tab: String,
+    pub screens: HashMap<String, ScreenState>,
+}
+
+/// Tab title without a trailing count, e.g. `Inbox` for `Inbox (3)`
+pub fn tab_name(title: &str) -> &str {
+    match title.rfind(" (") {
+        Some(i) if title.ends_with(')') => &title[..i],
+        _ => title,
+    }
+}
+
+impl UiState {
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(STATE_DIR).join(STATE_FILE))
+    }
+
+    pub fn load() -> Option<UiState> {
+        let contents = fs::read_to_string(UiState::path()?).ok()?;
+        serde_json::from_str(contents.as_str()).ok()
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let path = match UiState::path() {
+            Some(p) => p,
+            None => return Err(Error::new(ErrorKind::NotFound, "Could not find data directory")),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string(self)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        fs::write(path, contents)
+    }
+}
@@ -0,0 +1,239 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::{Duration, Instant};
+
+use futures::future::{join_all, BoxFuture, FutureExt, Shared};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::history;
+use crate::hn_api::{fetch, item_path};
+
+/// A story or comment from the API, shared by every tab and thread showing it
+pub type Item = Arc<Map<String, Value>>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    // Most items kept in memory, the least recently used are dropped first
+    pub max_items: usize,
+    // Seconds a kept item is used for before it is fetched again
+    pub max_age_secs: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> CacheConfig {
+        CacheConfig {
+            max_items: 5000,
+            max_age_secs: 300,
+        }
+    }
+}
+
+// A fetch that hasn't finished yet, awaited by everyone asking for the same id
+type Pending = Shared<BoxFuture<'static, Option<Item>>>;
+
+struct Entry {
+    item: Item,
+    fetched: Instant,
+    // Value of `ItemStore::uses` when the item was last asked for
+    used: u64,
+}
+
+/// Items fetched so far, by id
+struct ItemStore {
+    config: CacheConfig,
+    entries: HashMap<i64, Entry>,
+    // Ids by when they were last used, least recently used first
+    recent: BTreeMap<u64, i64>,
+    uses: u64,
+    pending: HashMap<i64, Pending>,
+}
+
+// Set up by `init`, the defaults are used until then
+static STORE: OnceLock<Mutex<ItemStore>> = OnceLock::new();
+
+/// Sets how many items are kept and for how long
+pub fn init(config: &CacheConfig) {
+    let _ = STORE.set(Mutex::new(ItemStore::new(config)));
+}
+
+fn store() -> MutexGuard<'static, ItemStore> {
+    let store = STORE.get_or_init(|| Mutex::new(ItemStore::new(&CacheConfig::default())));
+    // Nothing is left half updated while the lock is held, so a panic elsewhere can't hurt it
+    store.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl ItemStore {
+    fn new(config: &CacheConfig) -> ItemStore {
+        ItemStore {
+            config: config.clone(),
+            entries: HashMap::new(),
+            recent: BTreeMap::new(),
+            uses: 0,
+            pending: HashMap::new(),
+        }
+    }
+
+    // Moves `id` to the most recently used end
+    fn touch(&mut self, id: i64) {
+        if let Some(entry) = self.entries.get_mut(&id) {
+            self.uses += 1;
+            self.recent.remove(&entry.used);
+            entry.used = self.uses;
+            self.recent.insert(self.uses, id);
+        }
+    }
+
+    // The kept copy of `id`, if it isn't too old
+    fn get(&mut self, id: i64) -> Option<Item> {
+        let max_age = Duration::from_secs(self.config.max_age_secs);
+        let entry = self.entries.get(&id).filter(|e| e.fetched.elapsed() < max_age)?;
+        let item = entry.item.clone();
+        self.touch(id);
+        Some(item)
+    }
+
+    fn insert(&mut self, id: i64, item: Item) {
+        if let Some(old) = self.entries.remove(&id) {
+            self.recent.remove(&old.used);
+        }
+        self.entries.insert(id, Entry { item, fetched: Instant::now(), used: 0 });
+        self.touch(id);
+        while self.entries.len() > self.config.max_items {
+            match self.recent.pop_first() {
+                Some((_, oldest)) => self.entries.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    // The fetch of `id` already under way, or a new one. The bool says whether it is new
+    fn pending(&mut self, client: &Client, id: i64) -> (Pending, bool) {
+        if let Some(pending) = self.pending.get(&id) {
+            return (pending.clone(), false);
+        }
+        let client = client.clone();
+        let pending = async move {
+            let item = match fetch(&client, item_path(id).as_str()).await {
+                Ok(Value::Object(item)) => Some(Arc::new(item)),
+                _ => None,
+            };
+            let mut store = store();
+            store.pending.remove(&id);
+            if let Some(item) = item.as_ref() {
+                store.insert(id, item.clone());
+            }
+            item
+        }.boxed().shared();
+        self.pending.insert(id, pending.clone());
+        (pending, true)
+    }
+}
+
+/// The items `ids` in the same order, kept copies first unless `refresh` is set. Ids that are
+/// already being fetched are waited for instead of fetched again. Items that fail to load come
+/// back empty, like deleted ones
+pub async fn load(client: &Client, ids: &[i64], refresh: bool) -> Vec<Item> {
+    let lookups: Vec<Result<Item, (Pending, bool)>> = {
+        let mut store = store();
+        ids.iter()
+            .map(|id| match store.get(*id) {
+                Some(item) if !refresh => Ok(item),
+                _ => Err(store.pending(client, *id)),
+            })
+            .collect()
+    };
+    let results = join_all(lookups.into_iter().map(|lookup| async move {
+        match lookup {
+            Ok(item) => (item, false),
+            Err((pending, new)) => (pending.await.unwrap_or_default(), new),
+        }
+    })).await;
+
+    // Only the caller that started a fetch records the item, so nothing is written twice
+    let fetched: Vec<Map<String, Value>> = results.iter()
+        .filter(|(item, new)| *new && !item.is_empty())
+        .map(|(item, _)| Map::clone(item))
+        .collect();
+    history::record(&fetched);
+    results.into_iter().map(|(item, _)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::join;
+    use tokio::runtime::Runtime;
+    use crate::test_server::{api, init_network};
+
+    fn item(id: i64) -> Item {
+        Arc::new(serde_json::json!({ "id": id }).as_object().unwrap().clone())
+    }
+
+    // Ids from least to most recently used, checking `recent` matches `entries`
+    fn order(store: &ItemStore) -> Vec<i64> {
+        assert_eq!(store.recent.len(), store.entries.len());
+        for (used, id) in store.recent.iter() {
+            assert_eq!(store.entries[id].used, *used);
+        }
+        store.recent.values().copied().collect()
+    }
+
+    fn fetches(id: i64) -> usize {
+        let path = format!("/{}", item_path(id));
+        api().requests().iter().filter(|r| r.path == path).count()
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut store = ItemStore::new(&CacheConfig { max_items: 3, max_age_secs: 300 });
+        for id in 1..=3 {
+            store.insert(id, item(id));
+        }
+        assert_eq!(order(&store), [1, 2, 3]);
+        assert!(store.get(1).is_some());
+        assert_eq!(order(&store), [2, 3, 1]);
+        store.insert(4, item(4));
+        assert_eq!(order(&store), [3, 1, 4]);
+        assert!(store.get(2).is_none());
+    }
+
+    #[test]
+    fn keeps_one_place_per_item() {
+        let mut store = ItemStore::new(&CacheConfig { max_items: 3, max_age_secs: 300 });
+        store.insert(1, item(1));
+        store.insert(2, item(2));
+        // Fetched again, it moves to the end instead of being listed twice
+        store.insert(1, item(1));
+        store.touch(2);
+        store.touch(2);
+        // Unknown ids aren't added
+        store.touch(9);
+        assert_eq!(order(&store), [1, 2]);
+    }
+
+    #[test]
+    fn refetches_old_items() {
+        let mut store = ItemStore::new(&CacheConfig { max_items: 3, max_age_secs: 0 });
+        store.insert(1, item(1));
+        assert!(store.get(1).is_none());
+    }
+
+    #[test]
+    fn coalesces_fetches_of_the_same_item() {
+        init_network();
+        let client = Client::new();
+        let mut rt = Runtime::new().unwrap();
+        let id = 4301;
+        let (a, b) = rt.block_on(join(load(&client, &[id], false), load(&client, &[id, id], false)));
+        assert_eq!(fetches(id), 1);
+        assert!(a.iter().chain(b.iter()).all(|item| item.get("id") == Some(&Value::from(id))));
+
+        rt.block_on(load(&client, &[id], false));
+        assert_eq!(fetches(id), 1);
+        rt.block_on(load(&client, &[id], true));
+        assert_eq!(fetches(id), 2);
+    }
+}
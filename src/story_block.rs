@@ -16,6 +16,7 @@ pub enum StoryType {
 }
 
 pub struct StoryBlock {
+    #[allow(dead_code)]
     pub stype: StoryType,
     pub title: String,
     pub n_comments: i64,
@@ -29,7 +30,7 @@ pub struct StoryBlock {
 
 impl StoryBlock {
     pub fn new(item: &Map<String, Value>) -> Option<StoryBlock> {
-        let stype = match item["type"].as_str() {
+        let stype = match item.get("type").and_then(|t| t.as_str()) {
             Some(t) => {
                 match t {
                     "job" => StoryType::Job,
@@ -95,8 +96,10 @@ impl StoryBlock {
             Text::raw(self.text.as_str()),
         ];
 
+        // Show the story title on the block since the list may be hidden by the layout
+        let title = format!("Info - {}", self.title);
         let mut block = Block::default()
-            .title("Info")
+            .title(title.as_str())
             .title_style(get_style(HNStyles::WhiteTitle))
             .borders(Borders::ALL)
             .style(get_style(HNStyles::WhiteBlock))
@@ -1,105 +1,377 @@
-use std::cmp::min;
+use std::collections::HashMap;
 
+use regex::Regex;
+use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use tui::backend::Backend;
 use tui::Frame;
 use tui::layout::Rect;
 use tui::style::Modifier;
-use tui::widgets::{Block, Borders, BorderType, List, ListState, Text};
+use tui::widgets::{Block, Borders, BorderType, ListState, Paragraph, Text};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 use crate::colors::*;
-use crate::hn_api::{get_items, get_stories, ListType};
+use crate::filter::{ListFilter, SortMode};
+use crate::hn_api::{get_stories, time_ago, ListType};
+use crate::output::{domain, int_field, plain_text, str_field};
+use crate::store::Item;
 
 const INITIAL_LOADED_ITEMS: usize = 20;
+const COMMENT_SNIPPET_LENGTH: usize = 120;
+// Lines each row takes up
+const ROW_HEIGHT: u16 = 2;
+// Narrowest a title gets before the domain is left out
+const MIN_TITLE_WIDTH: usize = 20;
+// Title prefixes drawn in orange, as HN does for its own sections
+const TINTED_PREFIXES: [&str; 3] = ["Ask HN:", "Show HN:", "Tell HN:"];
+// Title of rows still being fetched
+const LOADING: &str = "loading…";
+
+// `1 point`, `2 points`
+fn plural(n: i64, unit: &str) -> String {
+    if n == 1 { format!("{} {}", n, unit) } else { format!("{} {}s", n, unit) }
+}
+
+/// `text` cut down to `width` columns, ending in `…` when anything was cut
+fn truncate(text: &str, width: usize) -> String {
+    if text.width() <= width {
+        return text.to_string();
+    }
+    let mut out = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        let w = c.width().unwrap_or(0);
+        if used + w + 1 > width {
+            break;
+        }
+        used += w;
+        out.push(c);
+    }
+    if width > 0 {
+        out.push('…');
+    }
+    out
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListConfig {
+    // Rows ahead of the cursor that are loaded in the background, so scrolling doesn't wait
+    pub prefetch: usize,
+}
+
+impl Default for ListConfig {
+    fn default() -> ListConfig {
+        ListConfig {
+            prefetch: 10,
+        }
+    }
+}
 
 pub struct StoryList {
     pub state: ListState,
-    pub items: Vec<Map<String, Value>>,
+    pub items: Vec<Item>,
     pub ids: Vec<String>,
     pub titles: Vec<String>,
+    // Line shown under each title
+    pub details: Vec<String>,
     pub focused: bool,
+    // Whether each row has been fetched, `items` holds empty maps for the others
+    loaded: Vec<bool>,
+    // Whether each row has been handed out by `prefetch` to be fetched in the background
+    requested: Vec<bool>,
+    // First row drawn, used to map clicks to rows
+    offset: usize,
+    // Rows that fitted when the list was last drawn
+    height: usize,
+    // See `ListConfig::prefetch`
+    prefetch: usize,
+    // Pattern of the current search, matching rows are highlighted
+    search: Option<Regex>,
+    pub sort: SortMode,
+    pub filter: ListFilter,
+    // Indices of the rows shown, in order, while sorted or filtered. Only loaded rows are
+    // sorted or filtered, `None` shows every row in feed order
+    view: Option<Vec<usize>>,
+    // Rows shown when the view last asked for more of the feed, until that batch is in
+    grown_from: Option<usize>,
+    // Set once a batch added nothing to the view, so it stops pulling in the feed
+    stalled: bool,
 }
 
 impl StoryList {
+    // First line of a row: the story title, or the start of the text for comments, e.g.
+    // replies in the inbox
     fn to_title(item: &Map<String, Value>) -> String {
-        let score = match item.get("score") {
-            Some(s) => s.as_i64().expect("Could not convert score to int"),
-            None => 0
-        };
-        let author = match item.get("by") {
-            Some(s) => s.as_str().expect("Could not convert author to str"),
-            None => "None"
-        };
-        let n_comments = match item.get("descendants") {
-            Some(s) => s.as_i64().expect("Could not get number of descendants"),
-            None => 0
-        };
-        let name = match item.get("title") {
-            Some(t) => t.as_str().expect("Could not get title"),
-            None => ""
-        };
-
-        let title = format!("{:>4} points | {:>3} comments | {} by {} ",
-                            score, n_comments, name, author);
+        if str_field(item, "type") == "comment" {
+            let text = plain_text(str_field(item, "text"));
+            return text.split_whitespace().collect::<Vec<&str>>().join(" ")
+                .chars().take(COMMENT_SNIPPET_LENGTH).collect();
+        }
+        str_field(item, "title").to_string()
+    }
 
-        title.to_string()
+    // Second line of a row, HN's subtext. Jobs have no score or comments
+    fn to_details(item: &Map<String, Value>) -> String {
+        let age = time_ago(int_field(item, "time"));
+        match str_field(item, "type") {
+            _ if item.is_empty() => String::new(),
+            "comment" => format!("reply by {} {}", str_field(item, "by"), age),
+            "job" => age,
+            _ => format!("{} by {} {} | {}", plural(int_field(item, "score"), "point"), str_field(item, "by"), age,
+                         plural(int_field(item, "descendants"), "comment")),
+        }
     }
-    pub fn new(story_type: &ListType) -> StoryList {
-        let ids = get_stories(story_type).expect("Could not get IDs");
-        let items = get_items(&ids[..INITIAL_LOADED_ITEMS]).expect("Could not get items");
-        let titles = items.iter().map(|item| { StoryList::to_title(item) }).collect();
+
+    pub fn new(story_type: &ListType, config: &ListConfig) -> StoryList {
+        // A feed that can't be loaded starts out empty, the failure shows up in the status line
+        let ids = get_stories(story_type).unwrap_or_default();
         let mut state = ListState::default();
-        state.select(Some(0));
+        state.select(if ids.is_empty() { None } else { Some(0) });
+        // The rows themselves are fetched in the background, see `prefetch`
         StoryList {
             state,
-            items,
+            items: vec![Item::default(); ids.len()],
+            titles: vec![String::new(); ids.len()],
+            details: vec![String::new(); ids.len()],
+            loaded: vec![false; ids.len()],
+            requested: vec![false; ids.len()],
             ids,
-            titles,
             focused: true,
+            offset: 0,
+            height: 0,
+            prefetch: config.prefetch,
+            search: None,
+            sort: SortMode::Rank,
+            filter: ListFilter::default(),
+            view: None,
+            grown_from: None,
+            stalled: false,
         }
     }
 
-    pub fn next(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i >= self.items.len() - 1 {
-                    if i < self.ids.len() - 1 {
-                        let n_loads = min(self.ids.len() - i, INITIAL_LOADED_ITEMS);
-                        self.items.append(get_items(&self.ids[i + 1..i + n_loads])
-                            .expect("Could not get new item").as_mut());
-                        for item in &self.items[i + 1..i + n_loads] {
-                            self.titles.push(StoryList::to_title(item))
-                        }
-                        i + 1
-                    } else {
-                        0
-                    }
-                } else {
-                    i + 1
-                }
-            }
-            None => 0,
+    /// A list of already fetched items, such as comments, that doesn't page in more
+    pub fn from_items(items: Vec<Item>) -> StoryList {
+        let mut list = StoryList {
+            state: ListState::default(),
+            items: Vec::new(),
+            ids: Vec::new(),
+            titles: Vec::new(),
+            details: Vec::new(),
+            loaded: Vec::new(),
+            requested: Vec::new(),
+            focused: true,
+            offset: 0,
+            height: 0,
+            prefetch: 0,
+            search: None,
+            sort: SortMode::Rank,
+            filter: ListFilter::default(),
+            view: None,
+            grown_from: None,
+            stalled: false,
         };
-        self.state.select(Some(i));
+        list.set_items(items);
+        list
     }
 
-    pub fn previous(&mut self) {
-        let i = match self.state.selected() {
-            Some(i) => {
-                if i == 0 {
-                    self.items.len() - 1
-                } else {
-                    i - 1
-                }
+    /// Replaces the listed items, keeping the selection in range
+    pub fn set_items(&mut self, items: Vec<Item>) {
+        self.ids = items.iter().map(|item| int_field(item, "id").to_string()).collect();
+        self.titles = items.iter().map(|item| StoryList::to_title(item)).collect();
+        self.details = items.iter().map(|item| StoryList::to_details(item)).collect();
+        self.loaded = vec![true; items.len()];
+        self.requested = vec![false; items.len()];
+        self.items = items;
+        self.update_view(None);
+    }
+
+    /// Number of rows shown
+    pub fn shown(&self) -> usize {
+        self.view.as_ref().map_or(self.ids.len(), |v| v.len())
+    }
+
+    // Index into `items` of the row shown at `position`
+    fn row_at(&self, position: usize) -> usize {
+        self.view.as_ref().map_or(position, |v| v[position])
+    }
+
+    /// The item of the selected row
+    pub fn selected_item(&self) -> Option<&Item> {
+        let position = self.state.selected().filter(|p| *p < self.shown())?;
+        self.items.get(self.row_at(position))
+    }
+
+    /// Marks the selected row as not loaded when it failed to load, so it is fetched again.
+    /// Returns whether it will be. Lists of fetched items don't know the ids of failed ones
+    pub fn retry_selected(&mut self) -> bool {
+        let row = match self.state.selected().filter(|p| *p < self.shown()) {
+            Some(position) => self.row_at(position),
+            None => return false,
+        };
+        if !self.items[row].is_empty() || self.ids[row] == "0" {
+            return false;
+        }
+        self.loaded[row] = false;
+        self.requested[row] = false;
+        true
+    }
+
+    /// Shows the rows in `sort` order and only those passing `filter`, without fetching anything
+    pub fn set_view(&mut self, sort: SortMode, filter: ListFilter) {
+        self.sort = sort;
+        self.filter = filter;
+        self.grown_from = None;
+        self.stalled = false;
+        let selected = self.state.selected().filter(|p| *p < self.shown()).map(|p| self.row_at(p));
+        self.update_view(selected);
+    }
+
+    // Sorts and filters the rows again. The row at index `selected` stays selected when it is
+    // still shown, otherwise the selection is kept in range
+    fn update_view(&mut self, selected: Option<usize>) {
+        self.view = if self.sort == SortMode::Rank && self.filter.is_empty() {
+            None
+        } else {
+            let mut rows: Vec<usize> = (0..self.items.len())
+                .filter(|i| self.loaded[*i] && !self.items[*i].is_empty() && self.filter.matches(&self.items[*i]))
+                .collect();
+            self.sort.sort(&self.items, &mut rows);
+            Some(rows)
+        };
+        let position = selected.and_then(|row| match self.view.as_ref() {
+            Some(view) => view.iter().position(|r| *r == row),
+            None => Some(row),
+        });
+        let position = match position.or(self.state.selected()) {
+            _ if self.shown() == 0 => None,
+            Some(p) => Some(p.min(self.shown() - 1)),
+            None => Some(0),
+        };
+        self.state.select(position);
+    }
+
+    // Shows the fetched `item` in row `i`. Rows that failed stay blank rather than being
+    // fetched on every key press
+    fn fill(&mut self, i: usize, item: Item) {
+        self.titles[i] = StoryList::to_title(&item);
+        self.details[i] = StoryList::to_details(&item);
+        self.items[i] = item;
+        self.loaded[i] = true;
+    }
+
+    // Sorts and filters again to take in newly loaded rows
+    fn refresh_view(&mut self) {
+        if self.view.is_some() {
+            let selected = self.state.selected().filter(|p| *p < self.shown()).map(|p| self.row_at(p));
+            self.update_view(selected);
+        }
+    }
+
+    /// Fills in the rows not loaded yet whose stories are among `items`
+    pub fn receive(&mut self, items: &HashMap<i64, Item>) {
+        let mut found = false;
+        for i in 0..self.ids.len() {
+            if self.loaded[i] {
+                continue;
+            }
+            if let Some(item) = self.ids[i].parse().ok().and_then(|id: i64| items.get(&id)) {
+                self.fill(i, item.clone());
+                found = true;
+            }
+        }
+        if found {
+            self.refresh_view();
+        }
+        let waiting = (0..self.ids.len()).any(|i| self.requested[i] && !self.loaded[i]);
+        if !waiting {
+            if let Some(shown) = self.grown_from.take() {
+                self.stalled = self.shown() == shown;
+            }
+        }
+    }
+
+    /// Selects row `index`, or the last one when the list is shorter. Rows around it that
+    /// aren't loaded yet come with the next `prefetch`
+    pub fn select_index(&mut self, index: usize) {
+        if self.shown() == 0 {
+            return;
+        }
+        self.state.select(Some(index.min(self.shown() - 1)));
+    }
+
+    /// Ids of the next batch of rows to fetch in the background, when the cursor is within the
+    /// prefetch distance of rows that aren't loaded yet, nearest first, or when a sorted or
+    /// filtered view doesn't fill the screen. Rows handed out once aren't handed out again,
+    /// `receive` fills them in
+    pub fn prefetch(&mut self) -> Vec<i64> {
+        let reach = self.height + self.prefetch;
+        let growing = self.view.as_ref()
+            .map(|view| view.len())
+            .filter(|shown| *shown < self.height.max(1) && self.grown_from.is_none() && !self.stalled);
+        let wanted = |i: &usize| !self.loaded[*i] && !self.requested[*i];
+        let missing: Vec<usize> = match (self.view.as_ref(), growing) {
+            // Sorted or filtered lists grow with the next stories of the feed while they don't
+            // fill the screen, one batch at a time, until a batch shows nothing new
+            (Some(_), Some(_)) => (0..self.ids.len())
+                .filter(wanted)
+                .take(INITIAL_LOADED_ITEMS)
+                .collect(),
+            (Some(_), None) => Vec::new(),
+            (None, _) => {
+                let selected = match self.state.selected() {
+                    Some(s) => s,
+                    None => return Vec::new(),
+                };
+                let below = selected..(selected + reach).min(self.ids.len());
+                let above = (selected.saturating_sub(reach)..selected).rev();
+                below.chain(above)
+                    .filter(wanted)
+                    .take(INITIAL_LOADED_ITEMS)
+                    .collect()
             }
-            None => 0,
         };
-        self.state.select(Some(i));
+        if !missing.is_empty() {
+            self.grown_from = growing;
+        }
+        missing.into_iter()
+            .map(|i| {
+                self.requested[i] = true;
+                self.ids[i].parse().unwrap_or(0)
+            })
+            .collect()
+    }
+
+    pub fn next(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.select_index(i + 1);
+        }
+    }
+
+    pub fn previous(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.select_index(i.saturating_sub(1));
+        }
+    }
+
+    /// Moves a screenful down, or up when not `down`
+    pub fn page(&mut self, down: bool) {
+        if let Some(i) = self.state.selected() {
+            let page = self.height.max(1);
+            self.select_index(if down { i + page } else { i.saturating_sub(page) });
+        }
     }
 
     pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, chunk: Rect) {
+        let mut title = " Stories ".to_string();
+        if self.sort != SortMode::Rank {
+            title = format!("{}- by {} ", title, self.sort.name());
+        }
+        if !self.filter.is_empty() {
+            title = format!("{}- {} ", title, self.filter.describe());
+        }
         let mut block = Block::default()
-            .title(" Stories ")
+            .title(title.as_str())
             .title_style(get_style(HNStyles::WhiteTitle))
             .borders(Borders::ALL)
             .style(get_style(HNStyles::WhiteBlock))
@@ -109,12 +381,126 @@ impl StoryList {
             block = block.border_type(BorderType::Double);
         }
 
-        let items = self.titles.iter().map(|i| Text::raw(i));
-        let my_list = List::new(items)
+        let inner = block.inner(chunk);
+        self.height = (inner.height / ROW_HEIGHT) as usize;
+        self.update_offset(self.height);
+        let end = (self.offset + self.height).min(self.shown());
+        let text: Vec<Text> = (self.offset..end)
+            .flat_map(|i| self.row(i, inner.width as usize))
+            .collect();
+        let paragraph = Paragraph::new(text.iter())
             .block(block)
-            .style(get_style(HNStyles::WhiteBlock))
-            .highlight_style(get_style(HNStyles::WhiteBlock).modifier(Modifier::BOLD))
-            .highlight_symbol(">>");
-        f.render_stateful_widget(my_list, chunk, &mut self.state);
+            .style(get_style(HNStyles::WhiteBlock));
+        f.render_widget(paragraph, chunk);
+    }
+
+    // The two lines of the row at `position`: rank, title and domain, then the details lined
+    // up under the title
+    fn row(&self, position: usize, width: usize) -> Vec<Text<'static>> {
+        let i = self.row_at(position);
+        let item = &self.items[i];
+        let selected = self.state.selected() == Some(position);
+        let rank_width = self.ids.len().max(1).to_string().len();
+        let rank = format!("{}{:>w$}. ", if selected { ">>" } else { "  " }, position + 1, w = rank_width);
+        let room = width.saturating_sub(rank.width());
+
+        let plain = if selected {
+            get_style(HNStyles::WhiteBlock).modifier(Modifier::BOLD)
+        } else {
+            get_style(HNStyles::WhiteBlock)
+        };
+        let title_style = match self.search.as_ref() {
+            Some(pattern) if pattern.is_match(self.titles[i].as_str()) => get_style(HNStyles::OrangeBorder).modifier(Modifier::BOLD),
+            _ if str_field(item, "type") == "job" => plain.modifier(Modifier::ITALIC),
+            _ => plain,
+        };
+        let sub = get_style(HNStyles::Subtext);
+
+        // The domain goes first when there isn't room for both
+        let domain = domain(str_field(item, "url")).map(|d| format!(" ({})", d)).unwrap_or_default();
+        let domain = if room >= domain.width() + MIN_TITLE_WIDTH { domain } else { String::new() };
+        // Rows still being fetched show a placeholder
+        let title = match self.loaded[i] {
+            true => truncate(self.titles[i].as_str(), room - domain.width()),
+            false => truncate(LOADING, room),
+        };
+
+        let mut texts = vec![Text::styled(rank.clone(), if selected { plain } else { sub })];
+        match TINTED_PREFIXES.iter().find(|p| title.starts_with(*p)) {
+            Some(prefix) => {
+                let (tinted, rest) = title.split_at(prefix.len());
+                texts.push(Text::styled(tinted.to_string(), get_style(HNStyles::OrangeBorder).modifier(title_style.modifier)));
+                texts.push(Text::styled(rest.to_string(), title_style));
+            }
+            None => texts.push(Text::styled(title, title_style)),
+        }
+        texts.push(Text::styled(format!("{}\n", domain), sub));
+        texts.push(Text::styled(format!("{}{}\n", " ".repeat(rank.width()), truncate(self.details[i].as_str(), room)), sub));
+        texts
+    }
+
+    // Moves the drawn rows as little as possible to keep the selected one on screen
+    fn update_offset(&mut self, list_height: usize) {
+        self.offset = match self.state.selected() {
+            Some(selected) => {
+                if selected + 1 >= list_height + self.offset {
+                    (selected + 1).saturating_sub(list_height)
+                } else if selected < self.offset {
+                    selected
+                } else {
+                    self.offset
+                }
+            }
+            None => 0,
+        };
+    }
+
+    /// Highlights the titles matching `pattern`, returning how many match
+    pub fn search(&mut self, pattern: Option<&Regex>) -> usize {
+        self.search = pattern.cloned();
+        match pattern {
+            Some(p) => (0..self.shown()).filter(|pos| p.is_match(self.titles[self.row_at(*pos)].as_str())).count(),
+            None => 0,
+        }
+    }
+
+    /// Selects the next title matching the search after the selected one, or the previous
+    /// one when not `forward`, wrapping around. With `inclusive` the selected title itself
+    /// counts. Returns false when nothing matches
+    pub fn next_match(&mut self, forward: bool, inclusive: bool) -> bool {
+        let pattern = match self.search.as_ref() {
+            Some(p) => p,
+            None => return false,
+        };
+        let n = self.shown();
+        if n == 0 {
+            return false;
+        }
+        let start = match (self.state.selected(), inclusive) {
+            (Some(i), true) => i,
+            (Some(i), false) if forward => (i + 1) % n,
+            (Some(i), false) => (i + n - 1) % n,
+            (None, _) => 0,
+        };
+        let found = (0..n)
+            .map(|step| if forward { (start + step) % n } else { (start + n - step) % n })
+            .find(|pos| pattern.is_match(self.titles[self.row_at(*pos)].as_str()));
+        if let Some(i) = found {
+            self.state.select(Some(i));
+        }
+        found.is_some()
+    }
+
+    /// Selects the row drawn at terminal row `row` of `chunk`, returning whether one was hit
+    pub fn click(&mut self, chunk: Rect, row: u16) -> bool {
+        if row <= chunk.y || row >= chunk.bottom().saturating_sub(1) {
+            return false;
+        }
+        let index = self.offset + ((row - chunk.y - 1) / ROW_HEIGHT) as usize;
+        if index >= self.shown() {
+            return false;
+        }
+        self.select_index(index);
+        true
     }
 }
\ No newline at end of file
@@ -1,135 +1,550 @@
-use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
 
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use tui::{
     backend::Backend,
     Frame
 };
 use tui::layout::{Constraint, Direction, Layout, Rect};
 
-use crate::comment_block::CommentBlock;
-use crate::hn_api::ListType;
+use crate::comment_block::{Comment, CommentBlock};
+use crate::config::{LayoutConfig, LayoutMode};
+use crate::event::contains;
+use crate::export::{export, ExportConfig, ExportFormat};
+use crate::history;
+use crate::hn_api::{get_item, get_root, ListType};
+use crate::output::{int_field, plain_text, str_field};
+use crate::state::ScreenState;
 use crate::story_block::StoryBlock;
-use crate::story_list::StoryList;
+use crate::store::Item;
+use crate::story_list::{ListConfig, StoryList};
 
 // Struct to select each block to scroll
-enum Focus {
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Focus {
+    #[default]
     List,
     Info,
     Comments,
 }
 
+// Seconds a reply posted from here is shown while the API doesn't return it. It usually
+// catches up within a minute, a reply it still lacks after this was edited or deleted
+const PENDING_REPLY_LIFETIME: i64 = 15 * 60;
+
+// A reply posted from here, kept until the API returns it
+struct PendingReply {
+    story: i64,
+    parent: i64,
+    // Id HN gave it, when it told
+    id: Option<i64>,
+    comment: Comment,
+}
+
+// Current unix time
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as i64).unwrap_or(0)
+}
+
 pub struct StoryScreen {
     pub story_list: StoryList,
-    pub story_type: ListType,
     pub story_block: Option<StoryBlock>,
     pub comment_block: Option<CommentBlock>,
+    // The story currently shown in the info and comment panes
+    pub item: Option<Map<String, Value>>,
+    pending_replies: Vec<PendingReply>,
+    // Placeholder ids for pending replies count down from -1
+    next_pending_id: i64,
     focused: Focus,
+    // Where each pane was last drawn, empty when it is hidden
+    list_area: Rect,
+    info_area: Rect,
+    comments_area: Rect,
+    // Current search, kept for threads opened while it is active
+    search: Option<Regex>,
 }
 
 impl StoryScreen {
-    pub fn new(story_type: ListType) -> StoryScreen {
+    pub fn new(story_type: &ListType, config: &ListConfig) -> StoryScreen {
+        StoryScreen::from_list(StoryList::new(story_type, config))
+    }
+
+    pub fn from_list(story_list: StoryList) -> StoryScreen {
         StoryScreen {
-            story_list: StoryList::new(&story_type),
-            story_type,
+            story_list,
             story_block: None,
             comment_block: None,
+            item: None,
+            pending_replies: Vec::new(),
+            next_pending_id: 0,
             focused: Focus::List,
+            list_area: Rect::default(),
+            info_area: Rect::default(),
+            comments_area: Rect::default(),
+            search: None,
         }
     }
 
-    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, chunk: Rect) {
-        let story_chunks = Layout::default()
-            .direction(Direction::Vertical)
+    pub fn draw<B: Backend>(&mut self, f: &mut Frame<B>, chunk: Rect, layout: &LayoutConfig) {
+        let area = Layout::default()
             .margin(1)
+            .constraints([Constraint::Percentage(100)].as_ref())
+            .split(chunk)[0];
+        self.list_area = Rect::default();
+        self.info_area = Rect::default();
+        self.comments_area = Rect::default();
+
+        let reading_area = match layout.mode {
+            LayoutMode::Vertical | LayoutMode::Horizontal => {
+                let direction = if layout.mode == LayoutMode::Vertical {
+                    Direction::Vertical
+                } else {
+                    Direction::Horizontal
+                };
+                let chunks = Layout::default()
+                    .direction(direction)
+                    .constraints(
+                        [
+                            Constraint::Percentage(layout.list_percent),
+                            Constraint::Percentage(100 - layout.list_percent)
+                        ]
+                            .as_ref(),
+                    )
+                    .split(area);
+                self.list_area = chunks[0];
+                self.story_list.draw(f, chunks[0]);
+                chunks[1]
+            }
+            LayoutMode::Reading => area,
+            LayoutMode::Zoom => {
+                match self.focused {
+                    Focus::List => {
+                        self.list_area = area;
+                        self.story_list.draw(f, area)
+                    }
+                    Focus::Info => {
+                        if let Some(s) = self.story_block.as_mut() {
+                            self.info_area = area;
+                            s.draw(f, area)
+                        }
+                    }
+                    Focus::Comments => {
+                        if let Some(c) = self.comment_block.as_mut() {
+                            self.comments_area = area;
+                            c.draw(f, area)
+                        }
+                    }
+                }
+                return;
+            }
+        };
+
+        let reading_chunks = Layout::default()
+            .direction(Direction::Vertical)
             .constraints(
                 [
-                    Constraint::Percentage(30),
-                    Constraint::Min(4),
-                    Constraint::Percentage(70)
+                    Constraint::Percentage(layout.info_percent),
+                    Constraint::Percentage(100 - layout.info_percent)
                 ]
                     .as_ref(),
             )
-            .split(chunk);
-
-        self.story_list.draw(f, story_chunks[0]);
+            .split(reading_area);
 
-        match self.story_block.as_mut() {
-            Some(s) => s.draw(f, story_chunks[1]),
-            None => {}
+        if let Some(s) = self.story_block.as_mut() {
+            self.info_area = reading_chunks[0];
+            s.draw(f, reading_chunks[0])
         }
 
-        match self.comment_block.as_mut() {
-            Some(c) => c.draw(f, story_chunks[2]),
-            None => {}
+        if let Some(c) = self.comment_block.as_mut() {
+            self.comments_area = reading_chunks[1];
+            c.draw(f, reading_chunks[1])
         }
     }
-    pub fn down(&mut self) {
-        match self.focused {
-            Focus::List => { self.story_list.next() }
+    fn scroll(&mut self, pane: Focus, down: bool) {
+        match pane {
+            Focus::List => {
+                if down {
+                    self.story_list.next()
+                } else {
+                    self.story_list.previous()
+                }
+            }
             Focus::Info => {
-                match self.story_block.as_mut() {
-                    Some(s) => s.scroll_down(),
-                    None => {}
+                if let Some(s) = self.story_block.as_mut() {
+                    if down { s.scroll_down() } else { s.scroll_up() }
                 }
             }
             Focus::Comments => {
-                match self.comment_block.as_mut() {
-                    Some(c) => c.scroll_down(),
-                    None => {}
+                if let Some(c) = self.comment_block.as_mut() {
+                    if down { c.scroll_down() } else { c.scroll_up() }
                 }
             }
         }
     }
+    pub fn down(&mut self) {
+        self.scroll(self.focused, true)
+    }
+    /// Scrolls the focused pane by a screenful, down or up
+    pub fn page(&mut self, down: bool) {
+        let area = match self.focused {
+            Focus::List => return self.story_list.page(down),
+            Focus::Info => self.info_area,
+            Focus::Comments => self.comments_area,
+        };
+        for _ in 0..area.height.saturating_sub(2).max(1) {
+            self.scroll(self.focused, down);
+        }
+    }
+    /// Selects row `index` of the list, or its last row, and focuses the list
+    pub fn jump_to(&mut self, index: usize) {
+        self.story_list.select_index(index);
+        self.set_focus(Focus::List);
+    }
     pub fn up(&mut self) {
-        match self.focused {
-            Focus::List => { self.story_list.previous() }
-            Focus::Info => {
-                match self.story_block.as_mut() {
-                    Some(s) => s.scroll_up(),
-                    None => {}
-                }
+        self.scroll(self.focused, false)
+    }
+
+    fn pane_at(&self, x: u16, y: u16) -> Option<Focus> {
+        if contains(self.list_area, x, y) {
+            Some(Focus::List)
+        } else if contains(self.info_area, x, y) {
+            Some(Focus::Info)
+        } else if contains(self.comments_area, x, y) {
+            Some(Focus::Comments)
+        } else {
+            None
+        }
+    }
+
+    /// Scrolls whichever pane is under the mouse cursor
+    pub fn scroll_at(&mut self, x: u16, y: u16, down: bool) {
+        if let Some(pane) = self.pane_at(x, y) {
+            self.scroll(pane, down)
+        }
+    }
+
+    /// Handles a left click, `double` being set when it follows a click on the same cell.
+    /// Returns true when a double click on a list row asks for that item to be opened
+    pub fn click(&mut self, x: u16, y: u16, double: bool) -> bool {
+        let pane = match self.pane_at(x, y) {
+            Some(p) => p,
+            None => return false,
+        };
+        self.set_focus(pane);
+        match pane {
+            Focus::List => {
+                return self.story_list.click(self.list_area, y) && double;
             }
+            Focus::Info => {}
             Focus::Comments => {
-                match self.comment_block.as_mut() {
-                    Some(c) => c.scroll_up(),
-                    None => {}
+                if let Some(c) = self.comment_block.as_mut() {
+                    c.click(self.comments_area, y);
                 }
             }
+        }
+        false
+    }
+
+    /// Opens the selected row. Rows that failed to load can't be opened and are fetched again
+    pub fn select(&mut self) -> Result<(), Error> {
+        let item = match self.story_list.selected_item() {
+            Some(item) => item.clone(),
+            None => return Ok(()),
         };
+        // Items that failed to load, or that the API returned as `null`, have nothing to show
+        if item.is_empty() {
+            let message = if self.story_list.retry_selected() {
+                "This story could not be loaded, trying again"
+            } else {
+                "This story could not be loaded"
+            };
+            return Err(Error::new(ErrorKind::NotFound, message));
+        }
+        // Lists of comments open the comment in the context of its story
+        if str_field(&item, "type") == "comment" {
+            let (story, comment) = get_root(int_field(&item, "id").to_string().as_str())?;
+            self.open(&story, comment);
+            return Ok(());
+        }
+        self.open(&item, None);
+        Ok(())
+    }
+
+    /// Id of the item selected in the list
+    pub fn selected_id(&self) -> Option<i64> {
+        self.story_list.selected_item().map(|item| int_field(item, "id"))
     }
 
-    pub fn select(&mut self) {
-        let selected = self.story_list.state.selected().unwrap();
-        if let Some(s) = StoryBlock::new(self.story_list.items[selected].borrow()) {
+    /// Shows `item` in the info and comment panes. When `comment` is given that comment
+    /// is focused in the thread
+    pub fn open(&mut self, item: &Map<String, Value>, comment: Option<i64>) {
+        if let Some(s) = StoryBlock::new(item) {
             self.story_block.replace(s);
+            self.item.replace(item.clone());
+        };
+
+        self.comment_block = CommentBlock::new(item);
+        if let Some(c) = self.comment_block.as_mut() {
+            c.search(self.search.as_ref());
+        }
+        self.reconcile_replies();
+
+        let focus = match (comment, self.comment_block.as_mut()) {
+            (Some(id), Some(c)) => {
+                c.focus_comment(id);
+                Focus::Comments
+            }
+            (_, None) if self.focused == Focus::Comments => Focus::Info,
+            _ => self.focused,
+        };
+        self.set_focus(focus);
+    }
+
+    /// Ids to fetch in the background: the stories coming up in the list, or else the next
+    /// replies of the open thread
+    pub fn load_more(&mut self) -> Vec<i64> {
+        let ids = self.story_list.prefetch();
+        if !ids.is_empty() {
+            return ids;
+        }
+        self.comment_block.as_mut().map(|c| c.load_more()).unwrap_or_default()
+    }
+
+    /// Takes in items fetched in the background
+    pub fn receive(&mut self, items: &HashMap<i64, Item>) {
+        self.story_list.receive(items);
+        if let Some(c) = self.comment_block.as_mut() {
+            c.receive(items);
+        }
+    }
+
+    /// The selection, open story, comment scroll and focus, to restore on the next launch
+    pub fn state(&self) -> ScreenState {
+        ScreenState {
+            selected: self.selected_id(),
+            story: self.story_id(),
+            comment: self.comment_block.as_ref().and_then(|c| c.current()),
+            comment_scroll: self.comment_block.as_ref().map(|c| c.scroll_position()).unwrap_or(0),
+            focus: self.focused,
+        }
+    }
+
+    /// Goes back to a saved state. Stories that can't be loaded any more are skipped
+    pub fn restore(&mut self, state: &ScreenState) {
+        if let Some(i) = state.selected.and_then(|id| self.story_list.ids.iter().position(|s| *s == id.to_string())) {
+            self.story_list.select_index(i);
+        }
+        if let Some(id) = state.story {
+            let story = history::cached(id).or_else(|| get_item(id.to_string().as_str()).ok());
+            if let Some(story) = story {
+                self.open(&story, state.comment);
+                if let Some(c) = self.comment_block.as_mut() {
+                    c.set_scroll_position(state.comment_scroll);
+                }
+            }
+        }
+        let focus = match state.focus {
+            Focus::Info if self.story_block.is_none() => Focus::List,
+            Focus::Comments if self.comment_block.is_none() => Focus::List,
+            focus => focus,
         };
+        self.set_focus(focus);
+    }
 
-        if let Some(c) = CommentBlock::new(self.story_list.items[selected].borrow()) {
-            self.comment_block.replace(c);
+    /// Highlights matches of `pattern` in the list and the thread, returning how many
+    /// titles and comments match
+    pub fn search(&mut self, pattern: Option<&Regex>) -> usize {
+        self.search = pattern.cloned();
+        let comments = self.comment_block.as_mut().map(|c| c.search(pattern)).unwrap_or(0);
+        self.story_list.search(pattern) + comments
+    }
+
+    /// Jumps to the next or previous match in the list when it is focused, otherwise in the thread
+    pub fn next_match(&mut self, forward: bool, inclusive: bool) -> bool {
+        match (self.focused, self.comment_block.as_mut()) {
+            (Focus::List, _) | (_, None) => self.story_list.next_match(forward, inclusive),
+            (_, Some(c)) => {
+                let found = c.next_match(forward, inclusive);
+                if found {
+                    self.set_focus(Focus::Comments);
+                }
+                found
+            }
+        }
+    }
+
+    /// Focuses the comment `id` in the thread that is already open
+    pub fn show_comment(&mut self, id: i64) {
+        if let Some(c) = self.comment_block.as_mut() {
+            c.focus_comment(id);
+            self.set_focus(Focus::Comments);
+        }
+    }
+
+    fn story_id(&self) -> Option<i64> {
+        self.item.as_ref().and_then(|i| i.get("id")).and_then(|id| id.as_i64())
+    }
+
+    // Drops pending replies the API now returns, or still doesn't after a while, and shows
+    // the others again
+    fn reconcile_replies(&mut self) {
+        let story = match self.story_id() {
+            Some(id) => id,
+            None => return,
         };
+        let now = now();
+        let pending = std::mem::take(&mut self.pending_replies);
+        for reply in pending {
+            if now - reply.comment.time > PENDING_REPLY_LIFETIME {
+                continue;
+            }
+            if reply.story == story {
+                let block = self.comment_block.get_or_insert_with(|| CommentBlock::from_comments(Vec::new()));
+                let parent = Some(reply.parent).filter(|p| *p != story);
+                if block.has_reply(parent, reply.id, &reply.comment) {
+                    continue;
+                }
+                block.add_reply(reply.parent, reply.comment.clone());
+            }
+            self.pending_replies.push(reply);
+        }
+    }
+
+    /// The item a reply would go to, with its text to quote
+    pub fn reply_target(&self) -> Option<(i64, String)> {
+        let id = self.target()?;
+        let text = match self.comment_block.as_ref().and_then(|c| c.text_of(id)) {
+            Some(t) => plain_text(t),
+            None => {
+                let item = self.item.as_ref()?;
+                match str_field(item, "text") {
+                    "" => str_field(item, "title").to_string(),
+                    t => plain_text(t),
+                }
+            }
+        };
+        Some((id, text))
+    }
+
+    /// Shows a reply that was just posted until the API catches up with it. `id` is the one
+    /// HN gave it, if known
+    pub fn add_reply(&mut self, parent: i64, id: Option<i64>, author: &str, text: &str) {
+        let story = match self.story_id() {
+            Some(id) => id,
+            None => return,
+        };
+        // Escape the plain text the way HN stores comments, paragraphs separated by <p>
+        let paragraphs: Vec<String> = text
+            .replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+            .split("\n\n")
+            .map(|p| p.trim().to_string())
+            .filter(|p| !p.is_empty())
+            .collect();
+        self.next_pending_id -= 1;
+        let comment = Comment {
+            id: self.next_pending_id,
+            author: author.to_string(),
+            time: now(),
+            text: paragraphs.join("<p>"),
+            replies: None,
+            kids: Vec::new(),
+            collapsed: false,
+            pending: true,
+            item: Item::default(),
+        };
+        self.comment_block
+            .get_or_insert_with(|| CommentBlock::from_comments(Vec::new()))
+            .add_reply(parent, comment.clone());
+        self.pending_replies.push(PendingReply { story, parent, id, comment });
+        self.set_focus(Focus::Comments);
+    }
+
+    /// Writes the open story and its comments to a file in the configured export directory
+    pub fn export(&mut self, config: &ExportConfig, format: ExportFormat) -> Result<PathBuf, Error> {
+        let item = match self.item.as_ref() {
+            Some(i) => i,
+            None => return Err(Error::new(ErrorKind::NotFound, "No story is open")),
+        };
+        // Exports have the whole thread, not just what has been loaded so far
+        if let Some(c) = self.comment_block.as_mut() {
+            c.load_all()?;
+        }
+        let comments = match self.comment_block.as_ref() {
+            Some(c) => c.comments.as_slice(),
+            None => &[],
+        };
+        export(config, item, comments, format)
+    }
+
+    /// Id of the comment selected in the comments pane when it is focused, otherwise of the open story
+    pub fn target(&self) -> Option<i64> {
+        if let (Focus::Comments, Some(c)) = (self.focused, self.comment_block.as_ref()) {
+            if let Some(id) = c.current() {
+                return Some(id);
+            }
+        }
+        self.story_id()
+    }
+
+    /// The highlighted comment when the comments are focused, the open story when the info
+    /// box is, otherwise the story selected in the list
+    pub fn inspected(&self) -> Option<Item> {
+        match (self.focused, self.comment_block.as_ref()) {
+            (Focus::Comments, Some(c)) => c.current().and_then(|id| c.item_of(id)).cloned(),
+            (Focus::Info, _) => self.item.clone().map(Item::new),
+            _ => self.story_list.selected_item().cloned(),
+        }
+    }
+
+    fn set_focus(&mut self, focus: Focus) {
+        self.focused = focus;
+        self.story_list.focused = focus == Focus::List;
+        if let Some(s) = self.story_block.as_mut() {
+            s.focused = focus == Focus::Info;
+        }
+        if let Some(c) = self.comment_block.as_mut() {
+            c.focused = focus == Focus::Comments;
+        }
     }
 
     pub fn focus(&mut self) {
         match self.focused {
             Focus::List => {
-                if let Some(s) = self.story_block.as_mut() {
-                    self.focused = Focus::Info;
-                    self.story_list.focused = false;
-                    s.focused = true;
+                if self.story_block.is_some() {
+                    self.set_focus(Focus::Info);
                 }
             }
             Focus::Info => {
-                if let Some(c) = self.comment_block.as_mut() {
-                    self.focused = Focus::Comments;
-                    self.story_block.as_mut().unwrap().focused = false;
-                    c.focused = true;
+                if self.comment_block.is_some() {
+                    self.set_focus(Focus::Comments);
+                } else {
+                    self.set_focus(Focus::List);
                 }
             }
-            Focus::Comments => {
-                self.focused = Focus::List;
-                self.comment_block.as_mut().unwrap().focused = false;
-                self.story_list.focused = true;
-            }
+            Focus::Comments => self.set_focus(Focus::List),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hn_api::get_items;
+    use crate::test_server::{init_network, TIMED_OUT_ITEMS};
+
+    #[test]
+    fn selects_stories_that_timed_out() {
+        init_network();
+        let items = get_items(&[TIMED_OUT_ITEMS.start.to_string()]).unwrap();
+        assert!(items[0].is_empty());
+        let mut screen = StoryScreen::from_list(StoryList::from_items(items));
+        assert_eq!(screen.select().unwrap_err().kind(), ErrorKind::NotFound);
+        assert!(screen.item.is_none());
+    }
+}
@@ -0,0 +1,100 @@
+use termion::event::Key;
+use tui::backend::Backend;
+use tui::Frame;
+use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
+use tui::widgets::{Block, Borders, BorderType, Clear, Paragraph, Text};
+
+use crate::colors::{get_style, HNStyles};
+use crate::prompt::PromptResult;
+
+const LABELS: [&str; 3] = ["Title", "URL", "Text"];
+const FORM_WIDTH: u16 = 70;
+const FORM_HEIGHT: u16 = 14;
+
+/// Modal dialog with the fields of HN's submit page
+pub struct SubmitForm {
+    pub fields: [String; 3],
+    focused: usize,
+}
+
+impl SubmitForm {
+    pub fn new() -> SubmitForm {
+        SubmitForm {
+            fields: [String::new(), String::new(), String::new()],
+            focused: 0,
+        }
+    }
+
+    pub fn title(&self) -> &str {
+        self.fields[0].as_str()
+    }
+    pub fn url(&self) -> &str {
+        self.fields[1].as_str()
+    }
+    pub fn text(&self) -> &str {
+        self.fields[2].as_str()
+    }
+
+    /// Tab and the arrow keys move between fields, Enter submits and Esc cancels
+    pub fn key(&mut self, key: Key) -> PromptResult {
+        match key {
+            Key::Char('\n') => return PromptResult::Submit(self.title().to_string()),
+            Key::Esc => return PromptResult::Cancel,
+            Key::Char('\t') | Key::Down => self.focused = (self.focused + 1) % LABELS.len(),
+            Key::BackTab | Key::Up => self.focused = (self.focused + LABELS.len() - 1) % LABELS.len(),
+            Key::Backspace => {
+                self.fields[self.focused].pop();
+            }
+            Key::Char(c) => self.fields[self.focused].push(c),
+            _ => {}
+        }
+        PromptResult::Editing
+    }
+
+    pub fn draw<B: Backend>(&self, f: &mut Frame<B>, screen: Rect) {
+        let width = FORM_WIDTH.min(screen.width);
+        let height = FORM_HEIGHT.min(screen.height);
+        let area = Rect::new(
+            screen.x + (screen.width - width) / 2,
+            screen.y + (screen.height - height) / 2,
+            width,
+            height,
+        );
+        f.render_widget(Clear, area);
+        let block = Block::default()
+            .title(" Submit (Tab: next field, Enter: submit, Esc: cancel) ")
+            .title_style(get_style(HNStyles::WhiteTitle))
+            .borders(Borders::ALL)
+            .border_type(BorderType::Double)
+            .border_style(get_style(HNStyles::OrangeBorder))
+            .style(get_style(HNStyles::WhiteBlock));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Length(3), Constraint::Min(3)].as_ref())
+            .split(inner);
+        for (i, label) in LABELS.iter().enumerate() {
+            let mut field = Block::default()
+                .title(label)
+                .title_style(get_style(HNStyles::WhiteTitle))
+                .borders(Borders::ALL)
+                .border_type(BorderType::Plain)
+                .border_style(get_style(HNStyles::OrangeBorder));
+            let cursor = if i == self.focused {
+                field = field.border_type(BorderType::Double);
+                "_"
+            } else {
+                ""
+            };
+            let text = [Text::raw(format!("{}{}", self.fields[i], cursor))];
+            let paragraph = Paragraph::new(text.iter())
+                .block(field)
+                .style(get_style(HNStyles::WhiteBlock))
+                .alignment(Alignment::Left)
+                .wrap(true);
+            f.render_widget(paragraph, chunks[i]);
+        }
+    }
+}
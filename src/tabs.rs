@@ -1,19 +1,91 @@
+use serde::{Deserialize, Serialize};
+use tui::layout::Rect;
+use unicode_width::UnicodeWidthStr;
+
+use crate::hn_api::{front_page, parse_day, ListType};
+
+/// A tab listing a feed, shown before the Watching, History and Inbox tabs
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedTab {
+    // Defaults to a title made from the feed
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub title: String,
+    #[serde(flatten)]
+    pub feed: ListType,
+    // Applied to the tab when it opens, written the way the filter bar takes it
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub filter: String,
+}
+
+impl FeedTab {
+    pub fn new(feed: ListType) -> FeedTab {
+        FeedTab {
+            title: String::new(),
+            feed,
+            filter: String::new(),
+        }
+    }
+
+    pub fn title(&self) -> String {
+        if self.title.is_empty() { self.feed.title() } else { self.title.clone() }
+    }
+
+    /// Reads a feed typed into the new tab prompt: `top`, `new`, `best`, `ask`, `show`, `job`,
+    /// `user NAME`, `front YYYY-MM-DD`, or otherwise a search where terms like `points>50`
+    /// become numeric filters
+    pub fn parse(input: &str) -> Result<FeedTab, String> {
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let feed = match words.as_slice() {
+            [] => return Err("type a feed or a search".to_string()),
+            ["top"] => ListType::TopStories,
+            ["new"] => ListType::NewStories,
+            ["best"] => ListType::BestStories,
+            ["ask"] => ListType::AskStories,
+            ["show"] => ListType::ShowStories,
+            ["job"] => ListType::JobStories,
+            ["user", name] => ListType::User { name: name.to_string() },
+            ["front", date] => front_page(parse_day(date)?),
+            _ => {
+                let (filters, terms): (Vec<&str>, Vec<&str>) = words.iter()
+                    .partition(|word| word.contains(['<', '>', '=']));
+                ListType::Search { query: terms.join(" "), numeric_filters: filters.join(",") }
+            }
+        };
+        Ok(FeedTab::new(feed))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabsConfig {
+    // Feed tabs in the order they are shown, saved whenever they are added, closed or moved
+    pub feeds: Vec<FeedTab>,
+}
+
+impl Default for TabsConfig {
+    fn default() -> TabsConfig {
+        TabsConfig {
+            feeds: vec![
+                FeedTab::new(ListType::TopStories),
+                FeedTab::new(ListType::NewStories),
+                FeedTab::new(ListType::BestStories),
+                FeedTab::new(ListType::AskStories),
+                FeedTab::new(ListType::ShowStories),
+                FeedTab::new(ListType::JobStories),
+            ],
+        }
+    }
+}
+
 pub struct TabsState {
     pub titles: Vec<String>,
     pub index: usize,
 }
 
 impl TabsState {
-    pub fn new() -> TabsState {
+    pub fn new(titles: Vec<String>) -> TabsState {
         TabsState {
-            titles: vec![
-                "Top Stories".to_string(),
-                "New Stories".to_string(),
-                "Best Stories".to_string(),
-                "Ask Stories".to_string(),
-                "Show Stories".to_string(),
-                "Job Stories".to_string()
-            ],
+            titles,
             index: 0,
         }
     }
@@ -28,4 +100,69 @@ impl TabsState {
             self.index = self.titles.len() - 1;
         }
     }
-}
\ No newline at end of file
+
+    /// Index of the tab title drawn at column `x` when the bordered `Tabs` widget
+    /// fills `area`, following the spacing `Tabs` uses between titles
+    pub fn index_at(&self, area: Rect, x: u16) -> Option<usize> {
+        let mut left = area.x + 1;
+        for (i, title) in self.titles.iter().enumerate() {
+            // One space of padding on each side of the title, then the divider
+            let right = left + title.width() as u16 + 2;
+            if x >= left && x < right {
+                return Some(i);
+            }
+            left = right + 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(query: &str, numeric_filters: &str) -> ListType {
+        ListType::Search { query: query.to_string(), numeric_filters: numeric_filters.to_string() }
+    }
+
+    #[test]
+    fn parses_feeds() {
+        for &(input, ref feed) in [
+            ("top", Ok(ListType::TopStories)),
+            (" job ", Ok(ListType::JobStories)),
+            ("user pg", Ok(ListType::User { name: "pg".to_string() })),
+            ("front 2026-10-01", Ok(ListType::FrontPage { date: "2026-10-01".to_string() })),
+            ("front 2026-13-01", Err("2026-13-01 is not a date like 2026-10-01".to_string())),
+            ("front yesterday", Err("yesterday is not a date like 2026-10-01".to_string())),
+            ("", Err("type a feed or a search".to_string())),
+            ("rust", Ok(search("rust", ""))),
+            ("rust points>50", Ok(search("rust", "points>50"))),
+            ("rust async points>50 num_comments>=10", Ok(search("rust async", "points>50,num_comments>=10"))),
+            // Words that only look like feeds search
+            ("top rust", Ok(search("top rust", ""))),
+            ("user", Ok(search("user", ""))),
+        ].iter() {
+            assert_eq!(&FeedTab::parse(input).map(|tab| tab.feed), feed, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn saves_tabs_as_toml() {
+        let mut feeds = TabsConfig::default().feeds;
+        feeds.push(FeedTab { title: "Rust".to_string(), feed: search("rust", "points>50"), filter: "comments:10".to_string() });
+        feeds.push(FeedTab::new(ListType::User { name: "pg".to_string() }));
+        feeds.push(FeedTab::new(ListType::FrontPage { date: "2026-10-01".to_string() }));
+        let text = toml::to_string_pretty(&TabsConfig { feeds: feeds.clone() }).unwrap();
+        assert!(text.contains("kind = 'top'"), "{}", text);
+        let read: TabsConfig = toml::from_str(text.as_str()).unwrap();
+        assert_eq!(read.feeds.len(), feeds.len());
+        for (read, feed) in read.feeds.iter().zip(feeds.iter()) {
+            assert_eq!((&read.title, &read.feed, &read.filter), (&feed.title, &feed.feed, &feed.filter));
+        }
+
+        let read: TabsConfig = toml::from_str("[[feeds]]\nkind = 'search'\nquery = 'rust'\n").unwrap();
+        assert_eq!(read.feeds[0].feed, search("rust", ""));
+        assert_eq!(read.feeds[0].title(), read.feeds[0].feed.title());
+        assert_eq!(toml::from_str::<TabsConfig>("").unwrap().feeds.len(), TabsConfig::default().feeds.len());
+    }
+}
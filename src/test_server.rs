@@ -0,0 +1,158 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A request the server received
+#[derive(Clone, Debug)]
+pub struct Request {
+    pub method: String,
+    // Path with the query string, e.g. `/item?id=1`
+    pub path: String,
+    // Header lines as sent, names lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    // When it arrived
+    pub time: Instant,
+}
+
+impl Request {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+/// What the server answers, after waiting `delay`
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+    pub delay: Duration,
+}
+
+impl Response {
+    pub fn new(status: u16, body: &str) -> Response {
+        Response { status, headers: Vec::new(), body: body.to_string(), delay: Duration::from_millis(0) }
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Response {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn delay(mut self, delay: Duration) -> Response {
+        self.delay = delay;
+        self
+    }
+}
+
+type Handler = dyn Fn(&Request, usize) -> Response + Send + Sync;
+
+/// An HTTP server on a free local port answering every request with `handler`, which is also
+/// given how many requests came before it. Runs until the test process exits
+pub struct TestServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl TestServer {
+    pub fn start<F>(handler: F) -> TestServer
+    where F: Fn(&Request, usize) -> Response + Send + Sync + 'static {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let handler: Arc<Handler> = Arc::new(handler);
+        let log = requests.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let handler = handler.clone();
+                let log = log.clone();
+                thread::spawn(move || serve(stream, handler.as_ref(), &log));
+            }
+        });
+        TestServer { url, requests }
+    }
+
+    /// Every request received so far, in order of arrival
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+fn serve(stream: TcpStream, handler: &Handler, log: &Mutex<Vec<Request>>) {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    if reader.read_line(&mut line).unwrap_or(0) == 0 {
+        return;
+    }
+    let mut words = line.split_whitespace();
+    let method = words.next().unwrap_or("").to_string();
+    let path = words.next().unwrap_or("").to_string();
+    let mut headers = Vec::new();
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_lowercase(), value.trim().to_string()));
+        }
+    }
+    let length = headers.iter()
+        .find(|(n, _)| n == "content-length")
+        .and_then(|(_, v)| v.parse().ok())
+        .unwrap_or(0);
+    let mut body = vec![0; length];
+    if reader.read_exact(&mut body).is_err() {
+        return;
+    }
+    let body = String::from_utf8_lossy(&body).to_string();
+    let request = Request { method, path, headers, body, time: Instant::now() };
+    let count = {
+        let mut log = log.lock().unwrap();
+        log.push(request.clone());
+        log.len() - 1
+    };
+    let response = handler(&request, count);
+    thread::sleep(response.delay);
+    let mut out = format!("HTTP/1.1 {} Status\r\nContent-Length: {}\r\nConnection: close\r\n",
+                          response.status, response.body.len());
+    for (name, value) in response.headers.iter() {
+        out.push_str(format!("{}: {}\r\n", name, value).as_str());
+    }
+    out.push_str("\r\n");
+    out.push_str(response.body.as_str());
+    let mut stream = reader.into_inner();
+    let _ = stream.write_all(out.as_bytes());
+}
+
+/// Items the shared API never answers for in time
+pub const TIMED_OUT_ITEMS: std::ops::Range<i64> = 9000..10000;
+
+/// The API every test shares, answering with `{"id": N}` for item N a little after being asked.
+/// Items in `TIMED_OUT_ITEMS` take longer than the timeout
+pub fn api() -> &'static TestServer {
+    static API: OnceLock<TestServer> = OnceLock::new();
+    API.get_or_init(|| TestServer::start(|request, _| {
+        let id = request.path.trim_start_matches("/item/").trim_end_matches(".json");
+        let delay = match id.parse() {
+            Ok(id) if TIMED_OUT_ITEMS.contains(&id) => Duration::from_millis(1500),
+            _ => Duration::from_millis(100),
+        };
+        Response::new(200, format!("{{\"id\":{}}}", id).as_str()).delay(delay)
+    }))
+}
+
+/// Sets the network config every test shares: `api` as the API, one second timeouts, two
+/// retries 50ms apart and at most 20 requests a second
+pub fn init_network() {
+    crate::hn_api::init(&crate::hn_api::NetworkConfig {
+        api_url: api().url.clone(),
+        timeout_secs: 1,
+        retries: 2,
+        backoff_ms: 50,
+        requests_per_second: 20,
+        ..Default::default()
+    });
+}
@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::hn_api::refresh_items;
+use crate::output::int_field;
+use crate::store::Item;
+
+const WATCH_DIR: &str = "hn_terminal";
+const WATCH_FILE: &str = "watching.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchConfig {
+    // How often the comment counts of watched stories are checked
+    pub interval_secs: u64,
+}
+
+impl Default for WatchConfig {
+    fn default() -> WatchConfig {
+        WatchConfig {
+            interval_secs: 120,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct Watched {
+    id: i64,
+    // `descendants` when the thread was last opened
+    seen: i64,
+    descendants: i64,
+    // When new comments were last noticed, or the story's time
+    activity: i64,
+}
+
+fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Stories followed in the Watching tab, with their comment counts kept between runs
+pub struct WatchList {
+    config: WatchConfig,
+    watched: Vec<Watched>,
+    // Latest fetch of each watched story
+    items: HashMap<i64, Item>,
+    last_poll: Option<Instant>,
+    // Whether a poll is running on a worker thread
+    polling: bool,
+}
+
+impl WatchList {
+    fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join(WATCH_DIR).join(WATCH_FILE))
+    }
+
+    pub fn load(config: &WatchConfig) -> WatchList {
+        let watched = WatchList::path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(contents.as_str()).ok())
+            .unwrap_or_default();
+        WatchList {
+            config: config.clone(),
+            watched,
+            items: HashMap::new(),
+            last_poll: None,
+            polling: false,
+        }
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let path = match WatchList::path() {
+            Some(p) => p,
+            None => return Err(Error::new(ErrorKind::NotFound, "Could not find data directory")),
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let contents = serde_json::to_string(&self.watched)
+            .map_err(|e| Error::new(ErrorKind::InvalidData, e.to_string()))?;
+        fs::write(path, contents)
+    }
+
+    /// Starts watching the story `item`, or stops if it is already watched. Returns whether
+    /// the story is watched now
+    pub fn toggle(&mut self, item: &Map<String, Value>) -> Result<bool, Error> {
+        let id = int_field(item, "id");
+        let watching = if self.watched.iter().any(|w| w.id == id) {
+            self.watched.retain(|w| w.id != id);
+            self.items.remove(&id);
+            false
+        } else {
+            let descendants = int_field(item, "descendants");
+            self.watched.push(Watched { id, seen: descendants, descendants, activity: int_field(item, "time") });
+            self.items.insert(id, Arc::new(item.clone()));
+            true
+        };
+        self.save()?;
+        Ok(watching)
+    }
+
+    pub fn due(&self) -> bool {
+        match self.last_poll {
+            _ if self.polling => false,
+            Some(t) => t.elapsed() >= Duration::from_secs(self.config.interval_secs),
+            None => true,
+        }
+    }
+
+    /// Starts a poll, returning the fetching of the watched stories to run on a worker thread
+    pub fn start_poll(&mut self) -> impl FnOnce() -> Result<Vec<Item>, Error> + Send + 'static {
+        self.last_poll = Some(Instant::now());
+        self.polling = true;
+        let ids: Vec<String> = self.watched.iter().map(|w| w.id.to_string()).collect();
+        move || refresh_items(ids.as_slice())
+    }
+
+    /// Takes in the stories fetched by `start_poll`, noting which gained comments
+    pub fn finish_poll(&mut self, items: Result<Vec<Item>, Error>) -> Result<(), Error> {
+        self.polling = false;
+        for item in items? {
+            let id = int_field(&item, "id");
+            // Stories unwatched during the poll are left out
+            if let Some(w) = self.watched.iter_mut().find(|w| w.id == id) {
+                let descendants = int_field(&item, "descendants");
+                if descendants > w.descendants {
+                    w.activity = now();
+                }
+                w.descendants = descendants;
+                self.items.insert(id, item);
+            }
+        }
+        self.save()
+    }
+
+    /// Records the thread `item` as read up to the comment count it was opened with
+    pub fn mark_seen(&mut self, item: &Map<String, Value>) {
+        let id = int_field(item, "id");
+        if let Some(w) = self.watched.iter_mut().find(|w| w.id == id) {
+            let descendants = int_field(item, "descendants");
+            if w.seen != descendants {
+                w.seen = descendants;
+                w.descendants = w.descendants.max(descendants);
+                self.items.insert(id, Arc::new(item.clone()));
+                // Losing this only shows the comments as new again
+                let _ = self.save();
+            }
+        }
+    }
+
+    /// Watched stories with their number of new comments, most recent activity first
+    pub fn rows(&self) -> Vec<(Item, i64)> {
+        let mut watched: Vec<&Watched> = self.watched.iter().collect();
+        watched.sort_by_key(|w| std::cmp::Reverse(w.activity));
+        watched.iter()
+            .filter_map(|w| self.items.get(&w.id).map(|item| (item.clone(), (w.descendants - w.seen).max(0))))
+            .collect()
+    }
+}